@@ -18,36 +18,38 @@ fn main() {
 
     // parse a chunk and print some of its data
     data.parse_gen8().unwrap();
-    println!("name = {}, display_name = {}", data.gen8.as_ref().unwrap().name, data.gen8.as_ref().unwrap().display_name);
+    println!("name = {}, display_name = {}", data.gen8.get().unwrap().name, data.gen8.get().unwrap().display_name);
 
     // parse the spritesheet chunk
     data.parse_txtr().unwrap();
-    println!("# of spritesheets = {}", data.txtr.as_ref().unwrap().spritesheets.len());
+    println!("# of spritesheets = {}", data.txtr.get().unwrap().spritesheets.len());
 
     // prints unloaded
-    match data.txtr.as_ref().unwrap().spritesheets[0].png {
+    match data.txtr.get().unwrap().spritesheets[0].png {
         PNGState::Loaded{..} => println!("sheet #0 image data is loaded"),
         PNGState::Unloaded{..} => println!("sheet #0 image data is unloaded"),
     }
 
     // load spritesheet image data
-    // requires the TXTR chunk to have been parsed already (parse_txtr)
+    // this is optional: loading sprites/backgrounds/fonts decodes the spritesheets they need on demand,
+    //   but if you're going to load everything anyway it's faster to decode them all up front
+    // this parses TXTR if it hasn't been parsed yet (so do the loaders for sprites, backgrounds and fonts)
     let start = Instant::now();
     data.load_spritesheets().unwrap();
     println!("load_spritesheets took {}ms", Instant::now().saturating_duration_since(start).as_millis());
     
     // now prints loaded
-    match data.txtr.as_ref().unwrap().spritesheets[0].png {
+    match data.txtr.get().unwrap().spritesheets[0].png {
         PNGState::Loaded{..} => println!("sheet #0 image data is loaded"),
         PNGState::Unloaded{..} => println!("sheet #0 image data is unloaded"),
     }
 
     // parse the sprites chunk
     data.parse_sprt().unwrap();
-    println!("# of sprites = {}", data.sprt.as_ref().unwrap().sprites.len());
+    println!("# of sprites = {}", data.sprt.get().unwrap().sprites.len());
 
     // prints unloaded
-    match data.sprt.as_ref().unwrap().sprites.values().next().unwrap().textures {
+    match data.sprt.get().unwrap().sprites.values().next().unwrap().textures {
         SpriteState::Loaded{..} => println!("sprite #0 image data is loaded"),
         SpriteState::Unloaded{..} => println!("sprite #0 image data is unloaded"),
     }
@@ -55,55 +57,60 @@ fn main() {
     // load image data for all sprites
     // alternatively, you could use data.load_sprite(String) to load individual sprites
     // requires the SPRT chunk to have been parsed already (parse_sprt)
-    // requires load_spritesheets to have been called already
+    // any spritesheets that weren't loaded by load_spritesheets are decoded on demand (and cached, see set_texture_cache_budget)
     data.load_sprites().unwrap();
     
     // now prints loaded
-    match data.sprt.as_ref().unwrap().sprites.values().next().unwrap().textures {
+    match data.sprt.get().unwrap().sprites.values().next().unwrap().textures {
         SpriteState::Loaded{..} => println!("sprite #0 image data is loaded"),
         SpriteState::Unloaded{..} => println!("sprite #0 image data is unloaded"),
     }
     // this allows you to read sprite images like this:
-    match &data.sprt.as_ref().unwrap().sprites.get("spr_krisplace").unwrap().textures {
-        SpriteState::Unloaded { texture_count, texture_addresses } => {},
+    match &data.sprt.get().unwrap().sprites.get("spr_krisplace").unwrap().textures {
+        SpriteState::Unloaded { .. } => {},
         SpriteState::Loaded { textures } => {
             println!("Here is spr_krisplace:");
             print_img(&textures[0]); // if the sprite has multiple frames, they are all in this Vec
         },
     }
 
+    // DataWin can also be used through &self (so it can be shared between threads, eg. in an Arc)
+    // these functions parse chunks and decode images on first access, and cache the results
+    let frames = data.sprite_frames("spr_krisplace").unwrap();
+    println!("spr_krisplace has {} frame(s)", frames.len());
+
     // load sounds like this:
     data.parse_sond().unwrap(); // (sond has the metadata and audo has the actual audio data)
     data.parse_audo().unwrap();
 
     data.load_sounds().unwrap(); // or load individual sounds with data.load_sound(String)
 
-    println!("# of sounds = {}", data.sond.as_ref().unwrap().sounds.len());
+    println!("# of sounds = {}", data.sond.get().unwrap().sounds.len());
 
     // now you can access the bytes of a sound file like this:
-    let sound = data.sond.as_ref().unwrap().sounds.get("snd_heartshot_dr_b").unwrap();
+    let sound = data.sond.get().unwrap().sounds.get("snd_heartshot_dr_b").unwrap();
     match sound.audio_data.as_ref().unwrap() {
-        dr_extract::chunk::AudioType::Internal(bytes) => {
-            println!("audio file is {} bytes", bytes.len());
+        dr_extract::chunk::AudioType::Internal { data, format } => {
+            println!("audio file is {} bytes ({})", data.len(), format.extension());
         },
-        dr_extract::chunk::AudioType::External => { /* sound.file contains the name of the external ogg file */},
+        dr_extract::chunk::AudioType::External { path } => { /* path is the external file, relative to the game directory (or use data.sound_data to read it) */ let _ = path; },
     }
 
     // parse & load fonts
     data.parse_font().unwrap();
     data.load_fonts().unwrap();
 
-    println!("# of fonts = {}", data.font.as_ref().unwrap().fonts.len());
+    println!("# of fonts = {}", data.font.get().unwrap().fonts.len());
 
     // now you can access the textures for each glyph of each font
-    let f: &FontEntry = data.font.as_ref().unwrap().fonts.get("fnt_main").unwrap();
-    let gl: &HashMap<u16, Glyph> = &f.glyphs;
+    let f: &FontEntry = data.font.get().unwrap().fonts.get("fnt_main").unwrap();
+    let gl: &HashMap<char, Glyph> = &f.glyphs;
 
-    let tex_Q: &DynamicImage = gl.get(&('Q' as u16)).unwrap().texture.as_ref().unwrap();
+    let tex_q: &DynamicImage = gl.get(&'Q').unwrap().texture.as_ref().unwrap();
     println!("Here is the letter Q:");
-    print_img(tex_Q);
+    print_img(tex_q);
 
-    let tex_ast: &DynamicImage = gl.get(&('*' as u16)).unwrap().texture.as_ref().unwrap();
+    let tex_ast: &DynamicImage = gl.get(&'*').unwrap().texture.as_ref().unwrap();
     println!("Here is an asterisk:");
     print_img(tex_ast);
 
@@ -0,0 +1,374 @@
+//! Full-canvas sprite frames, and (with the `animation` feature) exporting them as animated GIF/APNG/WebP.
+
+use image::{DynamicImage, GenericImageView, RgbaImage, imageops::{self, FilterType}};
+
+use crate::chunk::{SpriteEntry, SpriteState, TextureEntry};
+
+/// A frame's trimmed texture at the size it's drawn at (which is almost always the size it's stored at).
+pub(crate) fn frame_texture(texture: &DynamicImage, entry: &TextureEntry) -> RgbaImage {
+    let target = (u32::from(entry.bouding_x).max(1), u32::from(entry.bouding_y).max(1));
+    if texture.dimensions() == target {
+        texture.to_rgba8()
+    } else {
+        imageops::resize(texture, target.0, target.1, FilterType::Nearest)
+    }
+}
+
+impl SpriteEntry {
+    /// The size of the sprite's canvas, which every frame is placed on (and `origin_x`/`origin_y` are relative to).
+    #[must_use]
+    pub fn canvas_size(&self) -> (u32, u32) {
+        (self.width.max(1) as u32, self.height.max(1) as u32)
+    }
+
+    /// The loaded frames placed on the sprite's full canvas using their render offsets, so they line up with each other.
+    /// (The textures in `SpriteState::Loaded` are trimmed to their visible area.)
+    /// The sprite must be loaded first (see `DataWin::load_sprite`).
+    pub fn canvas_frames(&self) -> anyhow::Result<Vec<RgbaImage>> {
+        let (width, height) = self.canvas_size();
+        Ok(self.loaded_frames()?.map(|(texture, entry)| {
+            let mut canvas = RgbaImage::new(width, height);
+            imageops::replace(&mut canvas, &frame_texture(texture, entry), u32::from(entry.render_x), u32::from(entry.render_y));
+            canvas
+        }).collect())
+    }
+
+    /// Each loaded texture along with its TPAG entry.
+    pub(crate) fn loaded_frames(&self) -> anyhow::Result<impl Iterator<Item = (&DynamicImage, &TextureEntry)>> {
+        match &self.textures {
+            SpriteState::Loaded { textures } => Ok(textures.iter().zip(&self.frame_entries)),
+            SpriteState::Unloaded { .. } => Err(anyhow::anyhow!("Sprite must be loaded first!")),
+        }
+    }
+
+    /// Like `canvas_frames`, as `DynamicImage`s.
+    pub fn canvas_frame_images(&self) -> anyhow::Result<Vec<DynamicImage>> {
+        Ok(self.canvas_frames()?.into_iter().map(DynamicImage::ImageRgba8).collect())
+    }
+}
+
+#[cfg(feature = "animation")]
+pub use export::AnimationFormat;
+
+#[cfg(feature = "animation")]
+mod export {
+    use std::{fs::File, io::{BufWriter, Write}, path::Path};
+
+    use image::{Delay, Frame, RgbaImage, codecs::gif::{GifEncoder, Repeat}};
+
+    use crate::{ExportOptions, chunk::SpriteEntry};
+
+    /// The file format written by `SpriteEntry::export_animation`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum AnimationFormat {
+        Gif,
+        Apng,
+        WebP,
+    }
+
+    impl AnimationFormat {
+        /// The file extension for this format, without the dot.
+        #[must_use]
+        pub fn extension(self) -> &'static str {
+            match self {
+                AnimationFormat::Gif => "gif",
+                AnimationFormat::Apng => "png",
+                AnimationFormat::WebP => "webp",
+            }
+        }
+    }
+
+    impl SpriteEntry {
+        /// Writes the sprite's frames (see `canvas_frames`) as a looping animation at `fps` frames per second
+        /// (eg. `playback_fps(30.0)` for the speed it plays at in a 30 FPS game).
+        /// The sprite must be loaded first (see `DataWin::load_sprite`).
+        pub fn write_animation<W: Write>(&self, writer: W, format: AnimationFormat, fps: f32) -> anyhow::Result<()> {
+            self.write_animation_with(writer, format, fps, &ExportOptions::default())
+        }
+
+        /// Like `write_animation`, with each frame scaled and its alpha converted by `options` (animations always use the full canvas,
+        /// and GIFs are always indexed).
+        pub fn write_animation_with<W: Write>(&self, writer: W, format: AnimationFormat, fps: f32, options: &ExportOptions) -> anyhow::Result<()> {
+            if !(fps > 0.0 && fps.is_finite()) {
+                return Err(anyhow::anyhow!("Invalid animation speed: {} fps", fps));
+            }
+
+            let frames = self.canvas_frame_images()?.iter().map(|frame| options.apply(frame)).collect::<Vec<_>>();
+            if frames.is_empty() {
+                return Err(anyhow::anyhow!("Sprite has no frames to animate!"));
+            }
+
+            let frame_ms = 1000.0 / f64::from(fps);
+            match format {
+                AnimationFormat::Gif => write_gif(writer, frames, frame_ms),
+                AnimationFormat::Apng => write_apng(writer, &frames, frame_ms),
+                AnimationFormat::WebP => write_webp(writer, &frames, frame_ms),
+            }
+        }
+
+        /// Saves the sprite's frames as a looping animation, see `write_animation`.
+        pub fn export_animation<P: AsRef<Path>>(&self, path: P, format: AnimationFormat, fps: f32) -> anyhow::Result<()> {
+            self.export_animation_with(path, format, fps, &ExportOptions::default())
+        }
+
+        /// Saves the sprite's frames as a looping animation, see `write_animation_with`.
+        pub fn export_animation_with<P: AsRef<Path>>(&self, path: P, format: AnimationFormat, fps: f32, options: &ExportOptions) -> anyhow::Result<()> {
+            let mut writer = BufWriter::new(File::create(path)?);
+            self.write_animation_with(&mut writer, format, fps, options)?;
+            writer.flush()?;
+            Ok(())
+        }
+    }
+
+    fn write_gif<W: Write>(writer: W, frames: Vec<RgbaImage>, frame_ms: f64) -> anyhow::Result<()> {
+        // GIF delays are in hundredths of a second, so this gets rounded by the encoder
+        let delay = Delay::from_numer_denom_ms((frame_ms * 100.0).round() as u32, 100);
+
+        let mut gif = GifEncoder::new(writer);
+        gif.set_repeat(Repeat::Infinite)?;
+        gif.encode_frames(frames.into_iter().map(|frame| Frame::from_parts(frame, 0, 0, delay)))?;
+
+        Ok(())
+    }
+
+    fn write_apng<W: Write>(writer: W, frames: &[RgbaImage], frame_ms: f64) -> anyhow::Result<()> {
+        let (width, height) = frames[0].dimensions();
+
+        let mut encoder = apng::Encoder::new(writer, width, height);
+        encoder.set_color(apng::ColorType::Rgba);
+        encoder.set_depth(apng::BitDepth::Eight);
+        encoder.set_animated(frames.len() as u32, 0)?;
+        encoder.set_frame_delay(frame_ms.round().clamp(1.0, f64::from(u16::MAX)) as u16, 1000)?;
+        // every frame covers the whole canvas, so it just replaces the previous one
+        encoder.set_blend_op(apng::BlendOp::Source)?;
+        encoder.set_dispose_op(apng::DisposeOp::None)?;
+
+        let mut png = encoder.write_header()?;
+        for frame in frames {
+            png.write_image_data(frame.as_raw())?;
+        }
+        png.finish()?;
+
+        Ok(())
+    }
+
+    fn write_webp<W: Write>(mut writer: W, frames: &[RgbaImage], frame_ms: f64) -> anyhow::Result<()> {
+        let (width, height) = frames[0].dimensions();
+        let duration = (frame_ms.round() as u32).clamp(1, 0x00FF_FFFF);
+
+        // image-webp only writes still images, so each frame is encoded on its own and wrapped in an ANMF chunk
+        let mut body = Vec::new();
+        body.extend_from_slice(b"WEBP");
+
+        let mut vp8x = vec![0x10 | 0x02, 0, 0, 0]; // has alpha, is animated
+        vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        write_riff_chunk(&mut body, *b"VP8X", &vp8x);
+
+        let mut anim = vec![0; 4]; // transparent background
+        anim.extend_from_slice(&0_u16.to_le_bytes()); // loop forever
+        write_riff_chunk(&mut body, *b"ANIM", &anim);
+
+        for frame in frames {
+            let mut still = Vec::new();
+            image_webp::WebPEncoder::new(&mut still).encode(frame.as_raw(), width, height, image_webp::ColorType::Rgba8)?;
+
+            let mut anmf = vec![0; 6]; // frame x/y (always 0)
+            anmf.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+            anmf.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+            anmf.extend_from_slice(&duration.to_le_bytes()[..3]);
+            anmf.push(0x02); // don't blend with the previous frame
+            // skip the "RIFF....WEBP" header, leaving the VP8L chunk
+            anmf.extend_from_slice(&still[12..]);
+            write_riff_chunk(&mut body, *b"ANMF", &anmf);
+        }
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(body.len() as u32).to_le_bytes())?;
+        writer.write_all(&body)?;
+
+        Ok(())
+    }
+
+    fn write_riff_chunk(out: &mut Vec<u8>, id: [u8; 4], data: &[u8]) {
+        out.extend_from_slice(&id);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            out.push(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    /// A 4x3 sprite with a 2x2 red frame at (1, 1), and a 1x1 blue frame stretched to 2x1 at (0, 0).
+    fn sprite() -> SpriteEntry {
+        SpriteEntry::with_frames(4, 3, vec![
+            (DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, RED)), TextureEntry::placed(1, 1, 2, 2)),
+            (DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, BLUE)), TextureEntry::placed(0, 0, 2, 1)),
+        ])
+    }
+
+    #[test]
+    fn canvas_frames_are_placed_at_their_render_offsets() {
+        let frames = sprite().canvas_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+
+        let red = frames[0].enumerate_pixels().filter(|(_, _, px)| **px == RED).map(|(x, y, _)| (x, y)).collect::<Vec<_>>();
+        assert_eq!(frames[0].dimensions(), (4, 3));
+        assert_eq!(red, [(1, 1), (2, 1), (1, 2), (2, 2)]);
+        assert_eq!(frames[0].get_pixel(0, 0).0[3], 0);
+
+        assert_eq!(frames[1].get_pixel(0, 0), &BLUE);
+        assert_eq!(frames[1].get_pixel(1, 0), &BLUE);
+        assert_eq!(frames[1].get_pixel(2, 0).0[3], 0);
+    }
+
+    #[test]
+    fn canvas_needs_loaded_frames() {
+        let mut spr = sprite();
+        spr.textures = SpriteState::Unloaded { texture_count: 0, texture_addresses: Vec::new() };
+        assert!(spr.canvas_frames().is_err());
+
+        // a sprite without a size still gets a 1x1 canvas
+        spr.width = 0;
+        spr.height = -3;
+        assert_eq!(spr.canvas_size(), (1, 1));
+    }
+
+    #[cfg(feature = "animation")]
+    mod export {
+        use std::{convert::TryInto, io::Cursor};
+
+        use image::{AnimationDecoder, codecs::gif::GifDecoder};
+
+        use super::*;
+        use crate::{AnimationFormat, ExportOptions};
+
+        fn write(spr: &SpriteEntry, format: AnimationFormat, fps: f32, options: ExportOptions) -> Vec<u8> {
+            let mut out = Vec::new();
+            spr.write_animation_with(&mut out, format, fps, &options).unwrap();
+            out
+        }
+
+        fn u24(bytes: &[u8]) -> u32 {
+            u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16
+        }
+
+        /// The RIFF chunks after the "WEBP" tag, as (id, data) pairs.
+        fn riff_chunks(webp: &[u8]) -> Vec<([u8; 4], &[u8])> {
+            let mut chunks = Vec::new();
+            let mut pos = 12;
+            while pos < webp.len() {
+                let id = webp[pos..pos + 4].try_into().unwrap();
+                let len = u32::from_le_bytes(webp[pos + 4..pos + 8].try_into().unwrap()) as usize;
+                chunks.push((id, &webp[pos + 8..pos + 8 + len]));
+                pos += 8 + len + len % 2;
+            }
+            assert_eq!(pos, webp.len());
+            chunks
+        }
+
+        #[test]
+        fn webp_chunks() {
+            let webp = write(&sprite(), AnimationFormat::WebP, 20.0, ExportOptions::default());
+            assert_eq!(&webp[..4], b"RIFF");
+            assert_eq!(u32::from_le_bytes(webp[4..8].try_into().unwrap()) as usize, webp.len() - 8);
+            assert_eq!(&webp[8..12], b"WEBP");
+
+            let chunks = riff_chunks(&webp);
+            let ids = chunks.iter().map(|(id, _)| id).collect::<Vec<_>>();
+            assert_eq!(ids, [b"VP8X", b"ANIM", b"ANMF", b"ANMF"]);
+
+            let vp8x = chunks[0].1;
+            assert_eq!(vp8x[0], 0x12);
+            assert_eq!((u24(&vp8x[4..7]), u24(&vp8x[7..10])), (3, 2));
+            assert_eq!(chunks[1].1, [0, 0, 0, 0, 0, 0]);
+
+            for (_, anmf) in &chunks[2..] {
+                assert_eq!(&anmf[..6], [0; 6]);
+                assert_eq!((u24(&anmf[6..9]), u24(&anmf[9..12])), (3, 2));
+                assert_eq!(u24(&anmf[12..15]), 50);
+                assert_eq!(anmf[15], 0x02);
+                assert_eq!(&anmf[16..20], b"VP8L");
+            }
+        }
+
+        #[test]
+        fn webp_decodes() {
+            let webp = write(&sprite(), AnimationFormat::WebP, 20.0, ExportOptions::default());
+            let mut decoder = image_webp::WebPDecoder::new(Cursor::new(&webp)).unwrap();
+            assert!(decoder.is_animated());
+            assert_eq!(decoder.dimensions(), (4, 3));
+            assert_eq!(decoder.num_frames(), 2);
+
+            let expected = sprite().canvas_frames().unwrap();
+            let mut buf = vec![0; decoder.output_buffer_size().unwrap()];
+            for frame in &expected {
+                assert_eq!(decoder.read_frame(&mut buf).unwrap(), 50);
+                assert_eq!(&buf, frame.as_raw());
+            }
+        }
+
+        #[test]
+        fn apng_decodes() {
+            let png = write(&sprite(), AnimationFormat::Apng, 8.0, ExportOptions::default());
+            let mut reader = apng::Decoder::new(Cursor::new(&png)).read_info().unwrap();
+            assert_eq!(reader.info().animation_control.map(|actl| (actl.num_frames, actl.num_plays)), Some((2, 0)));
+
+            let expected = sprite().canvas_frames().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size()];
+            for frame in &expected {
+                reader.next_frame(&mut buf).unwrap();
+                let fctl = reader.info().frame_control.unwrap();
+                assert_eq!((fctl.delay_num, fctl.delay_den), (125, 1000));
+                assert_eq!(&buf, frame.as_raw());
+            }
+        }
+
+        #[test]
+        fn gif_decodes() {
+            let gif = write(&sprite(), AnimationFormat::Gif, 20.0, ExportOptions::default());
+            let frames = GifDecoder::new(Cursor::new(&gif)).unwrap().into_frames().collect_frames().unwrap();
+            assert_eq!(frames.len(), 2);
+            for frame in &frames {
+                assert_eq!(frame.delay().numer_denom_ms(), (50, 1));
+                assert_eq!(frame.buffer().dimensions(), (4, 3));
+            }
+            assert_eq!(frames[0].buffer().get_pixel(1, 1), &RED);
+            assert_eq!(frames[1].buffer().get_pixel(0, 0), &BLUE);
+        }
+
+        #[test]
+        fn scale_applies_to_every_format() {
+            let options = ExportOptions { scale: 2, ..ExportOptions::default() };
+
+            let webp = write(&sprite(), AnimationFormat::WebP, 20.0, options);
+            assert_eq!(image_webp::WebPDecoder::new(Cursor::new(&webp)).unwrap().dimensions(), (8, 6));
+
+            let png = write(&sprite(), AnimationFormat::Apng, 20.0, options);
+            let reader = apng::Decoder::new(Cursor::new(&png)).read_info().unwrap();
+            assert_eq!((reader.info().width, reader.info().height), (8, 6));
+
+            let gif = write(&sprite(), AnimationFormat::Gif, 20.0, options);
+            let frames = GifDecoder::new(Cursor::new(&gif)).unwrap().into_frames().collect_frames().unwrap();
+            assert_eq!(frames[0].buffer().dimensions(), (8, 6));
+        }
+
+        #[test]
+        fn invalid_animations() {
+            for fps in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+                assert!(sprite().write_animation(Vec::new(), AnimationFormat::Gif, fps).is_err(), "{} fps", fps);
+            }
+            assert!(SpriteEntry::with_frames(4, 3, Vec::new()).write_animation(Vec::new(), AnimationFormat::WebP, 20.0).is_err());
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Packing loaded sprites into a strip or atlas, with `TexturePacker`/Aseprite style JSON.
+
+use std::{convert::TryFrom, path::Path};
+
+use image::{RgbaImage, imageops};
+use serde_json::json;
+
+use crate::{ExportOptions, animation::frame_texture, chunk::SpriteEntry};
+
+/// How `Atlas::pack` lays out frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtlasLayout {
+    /// Full-canvas frames side by side, so every frame is the same size (no trimming).
+    Strip,
+    /// Trimmed frames packed into rows, with 1px of padding between them.
+    Packed,
+}
+
+/// One frame of a sprite in an `Atlas`.
+#[derive(Debug, Clone)]
+pub struct AtlasFrame {
+    pub sprite: String,
+    pub index: usize,
+    // where the frame is in the atlas
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub trimmed: bool,
+    // where the frame goes on the sprite's canvas (`render_x`/`render_y`)
+    pub offset_x: u32,
+    pub offset_y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub duration_ms: u32,
+}
+
+/// Sprite frames packed into one texture, see `Atlas::pack`.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    pub texture: RgbaImage,
+    pub frames: Vec<AtlasFrame>,
+}
+
+/// A frame waiting to be placed.
+struct Pending {
+    frame: AtlasFrame,
+    texture: RgbaImage,
+}
+
+impl Atlas {
+    /// Packs the frames of loaded sprites (see `DataWin::load_sprites`), in the order given.
+    /// Frame durations come from each sprite's speed, for a game running at `game_fps` (see `SpriteEntry::playback_fps`).
+    pub fn pack<'a, I: IntoIterator<Item = (&'a str, &'a SpriteEntry)>>(sprites: I, layout: AtlasLayout, game_fps: f32) -> anyhow::Result<Atlas> {
+        let mut pending = Vec::new();
+        for (name, spr) in sprites {
+            let (source_width, source_height) = spr.canvas_size();
+            let fps = spr.playback_fps(game_fps);
+            if !(fps > 0.0 && fps.is_finite()) {
+                return Err(anyhow::anyhow!("{}: invalid animation speed: {} fps", name, fps));
+            }
+            let duration_ms = (1000.0 / f64::from(fps)).round() as u32;
+
+            let frames = match layout {
+                AtlasLayout::Strip => spr.canvas_frames().map_err(|e| anyhow::anyhow!("{}: {}", name, e))?.into_iter().map(|texture| (texture, 0, 0)).collect::<Vec<_>>(),
+                AtlasLayout::Packed => spr.loaded_frames().map_err(|e| anyhow::anyhow!("{}: {}", name, e))?
+                    .map(|(texture, entry)| (frame_texture(texture, entry), u32::from(entry.render_x), u32::from(entry.render_y)))
+                    .collect(),
+            };
+
+            for (index, (texture, offset_x, offset_y)) in frames.into_iter().enumerate() {
+                pending.push(Pending {
+                    frame: AtlasFrame {
+                        sprite: name.to_string(),
+                        index,
+                        x: 0,
+                        y: 0,
+                        width: texture.width(),
+                        height: texture.height(),
+                        trimmed: (texture.width(), texture.height()) != (source_width, source_height),
+                        offset_x,
+                        offset_y,
+                        source_width,
+                        source_height,
+                        origin_x: spr.origin_x,
+                        origin_y: spr.origin_y,
+                        duration_ms,
+                    },
+                    texture,
+                });
+            }
+        }
+
+        let (width, height) = match layout {
+            AtlasLayout::Strip => place_strip(&mut pending),
+            AtlasLayout::Packed => place_rows(&mut pending),
+        };
+
+        let mut texture = RgbaImage::new(width.max(1), height.max(1));
+        for p in &pending {
+            imageops::replace(&mut texture, &p.texture, p.frame.x, p.frame.y);
+        }
+
+        Ok(Atlas {
+            texture,
+            frames: pending.into_iter().map(|p| p.frame).collect(),
+        })
+    }
+
+    /// The frame data in Aseprite's JSON array format (which `TexturePacker` can also write), referring to the texture as `image_file`.
+    /// Each sprite gets a frame tag covering its frames, and `origin` is the sprite's origin in pixels (`pivot` is the same, normalized).
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn to_json(&self, image_file: &str) -> serde_json::Value {
+        let frames = self.frames.iter().map(|frame| json!({
+            "filename": format!("{}_{}", frame.sprite, frame.index),
+            "frame": { "x": frame.x, "y": frame.y, "w": frame.width, "h": frame.height },
+            "rotated": false,
+            "trimmed": frame.trimmed,
+            "spriteSourceSize": { "x": frame.offset_x, "y": frame.offset_y, "w": frame.width, "h": frame.height },
+            "sourceSize": { "w": frame.source_width, "h": frame.source_height },
+            "origin": { "x": frame.origin_x, "y": frame.origin_y },
+            "pivot": { "x": frame.origin_x as f32 / frame.source_width as f32, "y": frame.origin_y as f32 / frame.source_height as f32 },
+            "duration": frame.duration_ms,
+        })).collect::<Vec<_>>();
+
+        // the frames of a sprite are always next to each other
+        let mut tags = Vec::new();
+        for (i, frame) in self.frames.iter().enumerate() {
+            if frame.index == 0 {
+                tags.push(json!({ "name": frame.sprite, "from": i, "to": i, "direction": "forward" }));
+            } else if let Some(tag) = tags.last_mut() {
+                tag["to"] = json!(i);
+            }
+        }
+
+        json!({
+            "frames": frames,
+            "meta": {
+                "app": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+                "image": image_file,
+                "format": "RGBA8888",
+                "size": { "w": self.texture.width(), "h": self.texture.height() },
+                "scale": "1",
+                "frameTags": tags,
+            },
+        })
+    }
+
+    /// The atlas upscaled by a whole number (nearest-neighbour), with every position and size in the frames scaled to match.
+    #[must_use]
+    pub fn scaled(&self, scale: u32) -> Atlas {
+        let scale = scale.max(1);
+        let iscale = i32::try_from(scale).unwrap_or(i32::MAX);
+        let (width, height) = self.texture.dimensions();
+
+        Atlas {
+            texture: imageops::resize(&self.texture, width * scale, height * scale, imageops::FilterType::Nearest),
+            frames: self.frames.iter().map(|frame| AtlasFrame {
+                x: frame.x * scale,
+                y: frame.y * scale,
+                width: frame.width * scale,
+                height: frame.height * scale,
+                offset_x: frame.offset_x * scale,
+                offset_y: frame.offset_y * scale,
+                source_width: frame.source_width * scale,
+                source_height: frame.source_height * scale,
+                origin_x: frame.origin_x * iscale,
+                origin_y: frame.origin_y * iscale,
+                ..frame.clone()
+            }).collect(),
+        }
+    }
+
+    /// Saves the texture as `{name}.png` and the JSON as `{name}.json` in `dir`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P, name: &str) -> anyhow::Result<()> {
+        self.save_with(dir, name, &ExportOptions::default())
+    }
+}
+
+impl SpriteEntry {
+    /// Packs this sprite's full-canvas frames into a horizontal strip, see `Atlas::pack`.
+    pub fn strip(&self, name: &str, game_fps: f32) -> anyhow::Result<Atlas> {
+        Atlas::pack(std::iter::once((name, self)), AtlasLayout::Strip, game_fps)
+    }
+}
+
+fn place_strip(pending: &mut [Pending]) -> (u32, u32) {
+    let mut x = 0;
+    let mut height = 0;
+    for p in pending {
+        p.frame.x = x;
+        x += p.frame.width;
+        height = height.max(p.frame.height);
+    }
+    (x, height)
+}
+
+/// Simple shelf packing: tallest frames first, in rows about as wide as the atlas would be if it were square.
+#[allow(clippy::cast_precision_loss)]
+fn place_rows(pending: &mut [Pending]) -> (u32, u32) {
+    const PADDING: u32 = 1;
+
+    let area: u64 = pending.iter().map(|p| u64::from(p.frame.width + PADDING) * u64::from(p.frame.height + PADDING)).sum();
+    let widest = pending.iter().map(|p| p.frame.width).max().unwrap_or(0);
+    let row_width = widest.max((area as f64).sqrt().ceil() as u32);
+
+    let mut order = (0..pending.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| std::cmp::Reverse(pending[*i].frame.height));
+
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    let mut width = 0;
+    for i in order {
+        let frame = &mut pending[i].frame;
+        if x > 0 && x + frame.width > row_width {
+            x = 0;
+            y += row_height + PADDING;
+            row_height = 0;
+        }
+
+        frame.x = x;
+        frame.y = y;
+        x += frame.width + PADDING;
+        row_height = row_height.max(frame.height);
+        width = width.max(frame.x + frame.width);
+    }
+
+    (width, y + row_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba};
+
+    use super::*;
+    use crate::chunk::{PlaybackSpeedType, SpriteState, TextureEntry};
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, Rgba(color)))
+    }
+
+    /// A 4x3 sprite with a 2x2 red frame at (1, 1) and a 3x1 green frame at (0, 2).
+    fn sprite() -> SpriteEntry {
+        SpriteEntry::with_frames(4, 3, vec![
+            (solid(2, 2, [255, 0, 0, 255]), TextureEntry::placed(1, 1, 2, 2)),
+            (solid(3, 1, [0, 255, 0, 255]), TextureEntry::placed(0, 2, 3, 1)),
+        ])
+    }
+
+    /// A 4x4 sprite with one full-size blue frame, playing at 10 FPS.
+    fn square() -> SpriteEntry {
+        let mut spr = SpriteEntry::with_frames(4, 4, vec![(solid(4, 4, [0, 0, 255, 255]), TextureEntry::placed(0, 0, 4, 4))]);
+        spr.playback_speed = Some(10.0);
+        spr.playback_speed_type = Some(PlaybackSpeedType::FramesPerSecond);
+        spr.origin_x = 2;
+        spr.origin_y = 3;
+        spr
+    }
+
+    /// Checks that each frame's pixels in the atlas are its own, and that no frames overlap.
+    fn assert_placed(atlas: &Atlas) {
+        for (i, frame) in atlas.frames.iter().enumerate() {
+            assert!(frame.x + frame.width <= atlas.texture.width() && frame.y + frame.height <= atlas.texture.height());
+            for other in &atlas.frames[i + 1..] {
+                let apart = frame.x + frame.width <= other.x || other.x + other.width <= frame.x
+                    || frame.y + frame.height <= other.y || other.y + other.height <= frame.y;
+                assert!(apart, "{:?} overlaps {:?}", frame, other);
+            }
+        }
+    }
+
+    #[test]
+    fn strip_uses_full_canvas_frames() {
+        let spr = sprite();
+        let atlas = spr.strip("spr", 30.0).unwrap();
+        assert_eq!(atlas.texture.dimensions(), (8, 3));
+        assert_placed(&atlas);
+
+        let frames = atlas.frames.iter().map(|f| (f.index, f.x, f.y, f.width, f.height, f.trimmed, f.offset_x, f.offset_y)).collect::<Vec<_>>();
+        assert_eq!(frames, [(0, 0, 0, 4, 3, false, 0, 0), (1, 4, 0, 4, 3, false, 0, 0)]);
+
+        // each frame is the sprite's canvas, with the texture at its render offset
+        let canvas = spr.canvas_frames().unwrap();
+        assert_eq!(imageops::crop_imm(&atlas.texture, 4, 0, 4, 3).to_image(), canvas[1]);
+        assert_eq!(atlas.texture.get_pixel(1, 1), &Rgba([255, 0, 0, 255]));
+        assert_eq!(atlas.texture.get_pixel(0, 0).0[3], 0);
+    }
+
+    #[test]
+    fn packed_frames_are_trimmed() {
+        let (spr, sq) = (sprite(), square());
+        let atlas = Atlas::pack(vec![("spr", &spr), ("sq", &sq)], AtlasLayout::Packed, 30.0).unwrap();
+        assert_placed(&atlas);
+
+        let frames = atlas.frames.iter().map(|f| (f.sprite.as_str(), f.index, f.width, f.height, f.trimmed, f.offset_x, f.offset_y)).collect::<Vec<_>>();
+        assert_eq!(frames, [("spr", 0, 2, 2, true, 1, 1), ("spr", 1, 3, 1, true, 0, 2), ("sq", 0, 4, 4, false, 0, 0)]);
+
+        // the tallest frame goes first, and there's padding between frames
+        assert_eq!((atlas.frames[2].x, atlas.frames[2].y), (0, 0));
+        assert_eq!(atlas.frames[0].x, 5);
+        for frame in &atlas.frames {
+            let texture = if frame.sprite == "sq" { &sq } else { &spr };
+            let SpriteState::Loaded { textures } = &texture.textures else { unreachable!() };
+            assert_eq!(imageops::crop_imm(&atlas.texture, frame.x, frame.y, frame.width, frame.height).to_image(), textures[frame.index].to_rgba8());
+        }
+    }
+
+    #[test]
+    fn durations_come_from_the_speed() {
+        let (spr, sq) = (sprite(), square());
+        let atlas = Atlas::pack(vec![("spr", &spr), ("sq", &sq)], AtlasLayout::Strip, 30.0).unwrap();
+        assert_eq!(atlas.frames.iter().map(|f| f.duration_ms).collect::<Vec<_>>(), [33, 33, 100]);
+
+        let mut stopped = square();
+        stopped.playback_speed = Some(0.0);
+        let err = Atlas::pack(vec![("stopped", &stopped)], AtlasLayout::Strip, 30.0).unwrap_err();
+        assert!(err.to_string().starts_with("stopped: "), "{}", err);
+    }
+
+    #[test]
+    fn sprites_must_be_loaded() {
+        let mut spr = sprite();
+        spr.textures = SpriteState::Unloaded { texture_count: 2, texture_addresses: vec![0, 0] };
+        for layout in [AtlasLayout::Strip, AtlasLayout::Packed] {
+            let err = Atlas::pack(vec![("spr", &spr)], layout, 30.0).unwrap_err();
+            assert!(err.to_string().starts_with("spr: "), "{}", err);
+        }
+    }
+
+    #[test]
+    fn empty_atlas() {
+        let atlas = Atlas::pack(Vec::new(), AtlasLayout::Packed, 30.0).unwrap();
+        assert_eq!(atlas.texture.dimensions(), (1, 1));
+        assert!(atlas.frames.is_empty());
+    }
+
+    #[test]
+    fn json_frames_and_tags() {
+        let (spr, sq) = (sprite(), square());
+        let atlas = Atlas::pack(vec![("spr", &spr), ("sq", &sq)], AtlasLayout::Packed, 30.0).unwrap();
+        let json = atlas.to_json("atlas.png");
+
+        let frame = &json["frames"][1];
+        assert_eq!(frame["filename"], "spr_1");
+        assert_eq!(frame["frame"], json!({ "x": atlas.frames[1].x, "y": atlas.frames[1].y, "w": 3, "h": 1 }));
+        assert_eq!(frame["trimmed"], true);
+        assert_eq!(frame["spriteSourceSize"], json!({ "x": 0, "y": 2, "w": 3, "h": 1 }));
+        assert_eq!(frame["sourceSize"], json!({ "w": 4, "h": 3 }));
+        assert_eq!(frame["duration"], 33);
+
+        let frame = &json["frames"][2];
+        assert_eq!(frame["origin"], json!({ "x": 2, "y": 3 }));
+        assert_eq!(frame["pivot"], json!({ "x": 0.5, "y": 0.75 }));
+
+        let meta = &json["meta"];
+        assert_eq!(meta["image"], "atlas.png");
+        assert_eq!(meta["size"], json!({ "w": atlas.texture.width(), "h": atlas.texture.height() }));
+        assert_eq!(meta["frameTags"], json!([
+            { "name": "spr", "from": 0, "to": 1, "direction": "forward" },
+            { "name": "sq", "from": 2, "to": 2, "direction": "forward" },
+        ]));
+    }
+}
@@ -0,0 +1,271 @@
+//! Decoding embedded audio (Ogg Vorbis or WAV) to PCM samples, behind the `audio` feature.
+
+use std::{fs::File, io::{BufWriter, Cursor, Seek, Write}, path::Path};
+
+use lewton::inside_ogg::OggStreamReader;
+
+use crate::chunk::{AudioFormat, AudioType, SoundEntry};
+
+/// Decoded audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pcm {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Vec<f32>, // interleaved by channel, in -1.0..=1.0
+}
+
+impl Pcm {
+    /// Decodes an Ogg Vorbis or WAV file.
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Pcm> {
+        match AudioFormat::detect(bytes) {
+            AudioFormat::Ogg => decode_vorbis(bytes),
+            AudioFormat::Wav => decode_wav(bytes),
+            format => Err(anyhow::anyhow!("Can't decode {:?} audio (only Ogg Vorbis and WAV are supported)!", format)),
+        }
+    }
+
+    /// The number of samples per channel.
+    #[must_use]
+    pub fn frames(&self) -> usize {
+        self.samples.len() / usize::from(self.channels.max(1))
+    }
+
+    /// The length in seconds.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn duration(&self) -> f64 {
+        self.frames() as f64 / f64::from(self.sample_rate.max(1))
+    }
+
+    /// The largest absolute sample value.
+    #[must_use]
+    pub fn peak(&self) -> f32 {
+        self.samples.iter().fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    /// Multiplies every sample by `gain`. Samples are clamped when they're written, so a gain > 1 can clip.
+    pub fn apply_gain(&mut self, gain: f32) {
+        for sample in &mut self.samples {
+            *sample *= gain;
+        }
+    }
+
+    /// Scales the samples so the loudest one is at `peak` (eg. 1.0 for full scale). Silence is left as is.
+    pub fn normalize(&mut self, peak: f32) {
+        let current = self.peak();
+        if current > 0.0 {
+            self.apply_gain(peak / current);
+        }
+    }
+
+    /// Changes the pitch the way playing at a different speed does (like GM's pitch), so a `pitch` of 2 is an octave up and half as long.
+    /// Uses linear interpolation, which is good enough for previews and sound effects.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn resample_pitch(&self, pitch: f32) -> Pcm {
+        let channels = usize::from(self.channels.max(1));
+        let frames = self.frames();
+        if pitch <= 0.0 || (pitch - 1.0).abs() < f32::EPSILON || frames == 0 {
+            return self.clone();
+        }
+
+        let step = f64::from(pitch);
+        let out_frames = (frames as f64 / step).floor() as usize;
+        let mut samples = Vec::with_capacity(out_frames * channels);
+
+        for i in 0..out_frames {
+            let pos = i as f64 * step;
+            let frame = pos as usize;
+            let next = (frame + 1).min(frames - 1);
+            let t = (pos - frame as f64) as f32;
+
+            for ch in 0..channels {
+                let a = self.samples[frame * channels + ch];
+                let b = self.samples[next * channels + ch];
+                samples.push(a + (b - a) * t);
+            }
+        }
+
+        Pcm {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            samples,
+        }
+    }
+
+    /// Applies a sound's volume and pitch, see `SoundEntry::render_as_played`.
+    #[must_use]
+    pub fn as_played(&self, volume: f32, pitch: f32) -> Pcm {
+        let mut pcm = self.resample_pitch(pitch);
+        pcm.apply_gain(volume);
+        pcm
+    }
+
+    /// Writes this as a 16-bit WAV file.
+    pub fn write_wav<W: Write + Seek>(&self, writer: W) -> anyhow::Result<()> {
+        let spec = hound::WavSpec {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut wav = hound::WavWriter::new(writer, spec)?;
+        for sample in &self.samples {
+            wav.write_sample((sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)?;
+        }
+        wav.finalize()?;
+
+        Ok(())
+    }
+
+    /// Saves this as a 16-bit WAV file.
+    pub fn export_wav<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.write_wav(BufWriter::new(File::create(path)?))
+    }
+}
+
+fn decode_vorbis(bytes: &[u8]) -> anyhow::Result<Pcm> {
+    let mut reader = OggStreamReader::new(Cursor::new(bytes))?;
+
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_itl()? {
+        samples.extend(packet.into_iter().map(|sample| f32::from(sample) / 32768.0));
+    }
+
+    Ok(Pcm {
+        sample_rate: reader.ident_hdr.audio_sample_rate,
+        channels: u16::from(reader.ident_hdr.audio_channels),
+        samples,
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn decode_wav(bytes: &[u8]) -> anyhow::Result<Pcm> {
+    let reader = hound::WavReader::new(Cursor::new(bytes))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader.into_samples::<i32>().map(|sample| sample.map(|sample| sample as f32 / scale)).collect::<Result<Vec<_>, _>>()?
+        },
+    };
+
+    Ok(Pcm {
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        samples,
+    })
+}
+
+impl SoundEntry {
+    /// Decodes this sound's embedded audio. It must be loaded first (see `DataWin::load_sound`).
+    /// For external sounds, use `DataWin::sound_pcm`.
+    pub fn decode(&self) -> anyhow::Result<Pcm> {
+        match &self.audio_data {
+            Some(AudioType::Internal { data, .. }) => Pcm::decode(data),
+            Some(AudioType::External { path }) => Err(anyhow::anyhow!("Sound is external ({}), use DataWin::sound_pcm to decode it!", path)),
+            None => Err(anyhow::anyhow!("Sound must be loaded before decoding it!")),
+        }
+    }
+
+    /// Decodes this sound's embedded audio with its `volume` and `pitch` applied, so it sounds like it does in game.
+    /// For external sounds, use `DataWin::render_sound`.
+    pub fn render_as_played(&self) -> anyhow::Result<Pcm> {
+        Ok(self.decode()?.as_played(self.volume, self.pitch))
+    }
+
+    /// Decodes this sound's embedded audio and saves it as a 16-bit WAV file, see `decode`.
+    pub fn export_wav<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.decode()?.export_wav(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16-bit mono 8 kHz WAV with these samples.
+    fn wav(samples: &[i16]) -> Vec<u8> {
+        let data_len = samples.len() as u32 * 2;
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt \x10\0\0\0\x01\0\x01\0");
+        bytes.extend_from_slice(&8000_u32.to_le_bytes());
+        bytes.extend_from_slice(&16000_u32.to_le_bytes());
+        bytes.extend_from_slice(b"\x02\0\x10\0data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_wav_samples_to_floats() {
+        let pcm = Pcm::decode(&wav(&[0, 16384, -32768, 32767])).unwrap();
+        assert_eq!((pcm.sample_rate, pcm.channels), (8000, 1));
+        assert_eq!(pcm.samples, [0.0, 0.5, -1.0, 32767.0 / 32768.0]);
+        assert_eq!(pcm.frames(), 4);
+        assert!((pcm.duration() - 0.0005).abs() < 1e-12);
+    }
+
+    #[test]
+    fn wav_files_round_trip() {
+        let pcm = Pcm { sample_rate: 22050, channels: 2, samples: vec![0.0, 0.25, -0.5, 2.0] };
+        let mut bytes = Cursor::new(Vec::new());
+        pcm.write_wav(&mut bytes).unwrap();
+
+        let decoded = Pcm::decode(bytes.get_ref()).unwrap();
+        assert_eq!((decoded.sample_rate, decoded.channels, decoded.frames()), (22050, 2, 2));
+        // written as 16-bit, and clamped
+        let expected = [0.0, 0.25, -0.5, 1.0];
+        assert!(decoded.samples.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-4), "{:?}", decoded.samples);
+    }
+
+    #[test]
+    fn other_formats_are_an_error() {
+        let err = Pcm::decode(b"ID3\x04\0\0\0\0\0\0").unwrap_err();
+        assert!(err.to_string().contains("Can't decode Mp3 audio"), "{}", err);
+    }
+
+    fn stereo(samples: &[f32]) -> Pcm {
+        Pcm { sample_rate: 8000, channels: 2, samples: samples.to_vec() }
+    }
+
+    #[test]
+    fn higher_pitches_are_shorter() {
+        let pcm = stereo(&[0.0, 1.0, 0.2, 0.8, 0.4, 0.6, 0.6, 0.4]);
+        assert_eq!(pcm.resample_pitch(2.0).samples, [0.0, 1.0, 0.4, 0.6]);
+        assert_eq!(pcm.resample_pitch(1.0), pcm);
+        assert_eq!(pcm.resample_pitch(0.0), pcm);
+    }
+
+    #[test]
+    fn lower_pitches_interpolate_between_frames() {
+        let pcm = stereo(&[0.0, 1.0, 0.2, 0.8]).resample_pitch(0.5);
+        let expected = [0.0, 1.0, 0.1, 0.9, 0.2, 0.8, 0.2, 0.8];
+        assert_eq!(pcm.frames(), 4);
+        assert!(pcm.samples.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", pcm.samples);
+    }
+
+    #[test]
+    fn as_played_applies_volume_and_pitch() {
+        let pcm = stereo(&[0.0, 1.0, 0.2, 0.8, 0.4, 0.6, 0.6, 0.4]).as_played(0.5, 2.0);
+        assert_eq!(pcm.samples, [0.0, 0.5, 0.2, 0.3]);
+        assert_eq!(pcm.sample_rate, 8000);
+    }
+
+    #[test]
+    fn normalize_scales_to_the_peak() {
+        let mut pcm = stereo(&[0.25, -0.5]);
+        pcm.normalize(1.0);
+        assert_eq!(pcm.samples, [0.5, -1.0]);
+
+        let mut silence = stereo(&[0.0, 0.0]);
+        silence.normalize(1.0);
+        assert_eq!(silence.samples, [0.0, 0.0]);
+    }
+}
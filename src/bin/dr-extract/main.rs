@@ -0,0 +1,447 @@
+//! Command-line tool for inspecting and extracting assets from a data.win.
+//!
+//! Build it with `cargo build --release --features cli`, then run `dr-extract --help`.
+//!
+//! Exit codes: 0 on success, 1 if something failed, 2 for invalid arguments.
+
+mod rewrap;
+
+use std::{fs, io::{self, IsTerminal, Write}, path::{Path, PathBuf}, process::ExitCode, time::Instant};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use dr_extract::{AlphaMode, AnimationFormat, Atlas, AtlasLayout, BmFontFormat, DataWin, ExportOptions, Phase, Progress, chunk::{AudioFormat, BackgroundState, PNGState, SpriteEntry, SpriteState, SpriteType}};
+use glob::Pattern;
+
+#[derive(Parser)]
+#[command(name = "dr-extract", version, about = "Inspect and extract assets from a GameMaker data.win")]
+struct Cli {
+    /// Path to the data.win
+    #[arg(short, long, default_value = "data.win", global = true)]
+    data: PathBuf,
+
+    /// Path to an audio group file (can be repeated, in order). Defaults to the files for the groups in the data.win's AGRP chunk, next to it
+    #[arg(short, long = "audiogroup", global = true)]
+    audiogroups: Vec<PathBuf>,
+
+    /// Don't print progress
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print general info about the game
+    Info,
+    /// List the chunks in the data.win
+    Chunks,
+    /// List the names of assets
+    List {
+        kind: Kind,
+    },
+    /// Extract assets to files
+    Extract {
+        kind: Kind,
+
+        /// Only extract assets whose name matches this glob (eg. "spr_kris_*")
+        #[arg(short, long)]
+        filter: Option<Pattern>,
+
+        /// Directory to extract into
+        #[arg(short, long, default_value = "extract")]
+        out: PathBuf,
+
+        /// Write animated sprites as a single animation in this format instead of a PNG per frame
+        #[arg(long, value_enum)]
+        animation: Option<Animation>,
+
+        /// Write sprites as a strip per sprite, or pack all of them into one atlas, with JSON frame data
+        #[arg(long, value_enum, conflicts_with = "animation")]
+        atlas: Option<Layout>,
+
+        /// Frames per second for --animation and --atlas. Defaults to each sprite's own speed
+        #[arg(long)]
+        fps: Option<f32>,
+
+        /// Frames per second the game runs at, for sprites whose speed is in frames per game frame
+        #[arg(long, default_value_t = 30.0)]
+        game_speed: f32,
+
+        /// Place sprite frames on the sprite's full canvas instead of trimming them to their visible area
+        #[arg(long)]
+        untrim: bool,
+
+        /// Upscale images by this whole number (nearest-neighbour), for pixel art. Atlas JSON and .fnt files are scaled to match
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        scale: u32,
+
+        /// Write colors premultiplied by alpha
+        #[arg(long)]
+        premultiply: bool,
+
+        /// Write palette-indexed PNGs for images with at most 256 colors
+        #[arg(long)]
+        indexed: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Layout {
+    Strip,
+    Packed,
+}
+
+/// How to write sprites.
+#[derive(Clone, Copy)]
+struct SpriteOutput {
+    animation: Option<AnimationFormat>,
+    atlas: Option<AtlasLayout>,
+    fps: Option<f32>,
+    game_speed: f32,
+}
+
+impl SpriteOutput {
+    fn fps(&self, spr: &SpriteEntry) -> f32 {
+        self.fps.unwrap_or_else(|| spr.playback_fps(self.game_speed))
+    }
+
+    /// Packs sprites, overriding their frame durations if --fps was given.
+    fn pack<'a>(&self, sprites: impl IntoIterator<Item = (&'a str, &'a SpriteEntry)>, layout: AtlasLayout) -> anyhow::Result<Atlas> {
+        let mut atlas = Atlas::pack(sprites, layout, self.game_speed)?;
+        if let Some(fps) = self.fps {
+            for frame in &mut atlas.frames {
+                frame.duration_ms = (1000.0 / fps).round() as u32;
+            }
+        }
+        Ok(atlas)
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Animation {
+    Gif,
+    Apng,
+    Webp,
+}
+
+impl From<Animation> for AnimationFormat {
+    fn from(animation: Animation) -> Self {
+        match animation {
+            Animation::Gif => AnimationFormat::Gif,
+            Animation::Apng => AnimationFormat::Apng,
+            Animation::Webp => AnimationFormat::WebP,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Kind {
+    Spritesheets,
+    Sprites,
+    Backgrounds,
+    Sounds,
+    Fonts,
+    All,
+}
+
+impl Kind {
+    const ALL: [Kind; 5] = [Kind::Spritesheets, Kind::Sprites, Kind::Backgrounds, Kind::Sounds, Kind::Fonts];
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Spritesheets => "spritesheet",
+            Kind::Sprites => "sprite",
+            Kind::Backgrounds => "background",
+            Kind::Sounds => "sound",
+            Kind::Fonts => "font",
+            Kind::All => "all",
+        }
+    }
+
+    fn expand(self) -> Vec<Kind> {
+        if self == Kind::All { Kind::ALL.to_vec() } else { vec![self] }
+    }
+}
+
+/// Prints progress to stderr (unless --quiet).
+#[derive(Clone, Copy)]
+struct Reporter {
+    quiet: bool,
+    tty: bool,
+}
+
+impl Reporter {
+    fn phase(&self, msg: &str) {
+        if !self.quiet {
+            eprintln!("{}...", msg);
+        }
+    }
+
+    fn progress(&self, done: usize, total: usize) {
+        // only show a running count when it won't flood a log file
+        if !self.quiet && self.tty {
+            eprint!("\r  {}/{}", done, total);
+            if done == total {
+                eprintln!();
+            }
+            let _ = io::stderr().flush();
+        }
+    }
+
+    fn took(&self, start: Instant) {
+        if !self.quiet {
+            eprintln!("  took {} ms", start.elapsed().as_millis());
+        }
+    }
+}
+
+impl Progress for Reporter {
+    fn report(&self, phase: Phase, done: usize, total: usize) {
+        // chunks parse quickly, so only the loaders are worth showing
+        if !matches!(phase, Phase::Parse(_)) {
+            self.progress(done, total);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run(cli: &Cli) -> anyhow::Result<()> {
+    let rep = Reporter {
+        quiet: cli.quiet,
+        tty: io::stderr().is_terminal(),
+    };
+
+    let mut data = open(cli)?;
+    data.set_progress(rep);
+
+    match &cli.command {
+        Command::Info => info(&data),
+        Command::Chunks => {
+            for chunk in data.chunks() {
+                println!("{} @ {:#010x}, {} bytes", String::from_utf8_lossy(&chunk.id), chunk.addr, chunk.len);
+            }
+            for group_id in 1..=data.audiogroup_count() {
+                println!();
+                println!("audio group {}:", group_id);
+                for chunk in data.audiogroup_chunks(group_id).unwrap_or_default() {
+                    println!("{} @ {:#010x}, {} bytes", String::from_utf8_lossy(&chunk.id), chunk.addr, chunk.len);
+                }
+            }
+            Ok(())
+        },
+        Command::List { kind } => {
+            let prefixed = *kind == Kind::All;
+            for kind in kind.expand() {
+                for name in names(&data, kind)? {
+                    let details = if kind == Kind::Sounds { sound_details(&data, &name) } else { String::new() };
+                    if prefixed {
+                        println!("{}: {}{}", kind.name(), name, details);
+                    } else {
+                        println!("{}{}", name, details);
+                    }
+                }
+            }
+            Ok(())
+        },
+        Command::Extract { kind, filter, out, animation, atlas, fps, game_speed, untrim, scale, premultiply, indexed } => {
+            let sprite_output = SpriteOutput {
+                animation: animation.map(AnimationFormat::from),
+                atlas: atlas.map(|atlas| match atlas {
+                    Layout::Strip => AtlasLayout::Strip,
+                    Layout::Packed => AtlasLayout::Packed,
+                }),
+                fps: *fps,
+                game_speed: *game_speed,
+            };
+            let options = ExportOptions {
+                trim: !untrim,
+                scale: *scale,
+                alpha: if *premultiply { AlphaMode::Premultiplied } else { AlphaMode::Straight },
+                indexed: *indexed,
+            };
+            for kind in kind.expand() {
+                let count = extract(&mut data, kind, filter.as_ref(), out, sprite_output, &options, &rep)?;
+                if !rep.quiet {
+                    eprintln!("Extracted {} {}(s) to {}", count, kind.name(), out.join(kind.name()).display());
+                }
+            }
+            Ok(())
+        },
+    }
+}
+
+fn open(cli: &Cli) -> anyhow::Result<DataWin> {
+    let mut data = if cli.audiogroups.is_empty() {
+        DataWin::open_file(&cli.data)?
+    } else {
+        let audiogroups = cli.audiogroups.iter().map(PathBuf::as_path).collect();
+        dr_extract::prepare_file(cli.data.as_path(), audiogroups)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", cli.data.display(), e))?
+            .fetch_chunks()?
+    };
+    data.add_background_rewrap_columns(rewrap::deltarune());
+    Ok(data)
+}
+
+fn info(data: &DataWin) -> anyhow::Result<()> {
+    let gen8 = data.parse_gen8()?;
+    println!("Name:           {}", gen8.name);
+    println!("Display name:   {}", gen8.display_name);
+    println!("File name:      {}", gen8.filename);
+    println!("Config:         {}", gen8.config);
+    println!("GM version:     {}.{}.{}.{}", gen8.major, gen8.minor, gen8.release, gen8.build);
+    println!("Game ID:        {}", gen8.game_id);
+    println!("Steam app ID:   {}", gen8.steam_app_id);
+    println!("Window size:    {}x{}", gen8.default_window_width, gen8.default_window_height);
+    println!("Timestamp:      {}", gen8.timestamp);
+
+    for kind in Kind::ALL {
+        match names(data, kind) {
+            Ok(names) => println!("{:<15} {}", format!("{}s:", kind.name()), names.len()),
+            Err(e) => println!("{:<15} ({})", format!("{}s:", kind.name()), e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Format and length of a sound, eg. `  (ogg, 44100 Hz, 2 ch, 1.52 s)`.
+fn sound_details(data: &DataWin, name: &str) -> String {
+    match data.sound_info(name) {
+        Ok(info) => {
+            let mut parts = vec![info.format.extension().to_string()];
+            parts.extend(info.sample_rate.map(|rate| format!("{} Hz", rate)));
+            parts.extend(info.channels.map(|channels| format!("{} ch", channels)));
+            parts.extend(info.duration.map(|duration| format!("{:.2} s", duration)));
+            format!("  ({})", parts.join(", "))
+        },
+        Err(_) => String::new(),
+    }
+}
+
+/// Names of all assets of a kind, sorted. Spritesheets are named by their index.
+fn names(data: &DataWin, kind: Kind) -> anyhow::Result<Vec<String>> {
+    let mut names: Vec<String> = match kind {
+        Kind::Spritesheets => return Ok((0..data.parse_txtr()?.spritesheets.len()).map(|i| i.to_string()).collect()),
+        Kind::Sprites => data.parse_sprt()?.sprites.keys().cloned().collect(),
+        Kind::Backgrounds => data.parse_bgnd()?.backgrounds.keys().cloned().collect(),
+        Kind::Sounds => data.parse_sond()?.sounds.keys().cloned().collect(),
+        Kind::Fonts => data.parse_font()?.fonts.keys().cloned().collect(),
+        Kind::All => Kind::ALL.iter().map(|kind| names(data, *kind)).collect::<anyhow::Result<Vec<_>>>()?.concat(),
+    };
+    names.sort();
+    Ok(names)
+}
+
+fn extract(data: &mut DataWin, kind: Kind, filter: Option<&Pattern>, out: &Path, sprite_output: SpriteOutput, options: &ExportOptions, rep: &Reporter) -> anyhow::Result<usize> {
+    // spelled out rather than `Option::is_none_or`, which needs Rust 1.82
+    let matches = |name: &str| match filter {
+        Some(filter) => filter.matches(name),
+        None => true,
+    };
+    let names = names(data, kind)?
+        .into_iter()
+        .filter(|name| matches(name))
+        .collect::<Vec<_>>();
+
+    let dir = out.join(kind.name());
+    fs::create_dir_all(&dir)?;
+
+    rep.phase(&format!("Loading {}s", kind.name()));
+    let start = Instant::now();
+    match kind {
+        Kind::Spritesheets => data.load_spritesheets()?,
+        Kind::Sprites => { data.load_sprites_where(matches)?; },
+        Kind::Backgrounds => { data.load_backgrounds_where(matches)?; },
+        // written straight from the AUDO chunks (or the external files) by sound_data
+        Kind::Sounds => { data.parse_audo()?; },
+        Kind::Fonts => { data.load_fonts_where(matches)?; },
+        Kind::All => unreachable!("extract is called for each kind"),
+    }
+    rep.took(start);
+
+    rep.phase(&format!("Writing {}s", kind.name()));
+    let start = Instant::now();
+    if kind == Kind::Sprites && sprite_output.atlas == Some(AtlasLayout::Packed) {
+        // all the sprites go in one atlas.png/atlas.json
+        //   (except SWF/Spine sprites, which are written as they are)
+        let sprt = data.parse_sprt()?;
+        let (bitmaps, others): (Vec<_>, Vec<_>) = names.iter().map(|name| (name.as_str(), &sprt.sprites[name])).partition(|(_, spr)| spr.sprite_type == SpriteType::Normal);
+        sprite_output.pack(bitmaps, AtlasLayout::Packed)?.save_with(&dir, "atlas", options)?;
+        for (name, spr) in others.into_iter().filter(|(_, spr)| spr.payload.is_some()) {
+            spr.export_payload(name, &dir).map_err(|e| anyhow::anyhow!("Failed to extract sprite {}: {}", name, e))?;
+        }
+    } else {
+        for (i, name) in names.iter().enumerate() {
+            write_asset(data, kind, name, &dir, sprite_output, options).map_err(|e| anyhow::anyhow!("Failed to extract {} {}: {}", kind.name(), name, e))?;
+            rep.progress(i + 1, names.len());
+        }
+    }
+    rep.took(start);
+
+    Ok(names.len())
+}
+
+fn write_asset(data: &DataWin, kind: Kind, name: &str, dir: &Path, sprite_output: SpriteOutput, options: &ExportOptions) -> anyhow::Result<()> {
+    match kind {
+        Kind::Spritesheets => {
+            let index: usize = name.parse()?;
+            if let Some(PNGState::Loaded { texture }) = data.txtr.get().map(|txtr| &txtr.spritesheets[index].png) {
+                texture.save(dir.join(format!("{}.png", name)))?;
+            }
+        },
+        Kind::Sprites => {
+            if let Some(spr) = data.sprt.get().and_then(|sprt| sprt.sprites.get(name)) {
+                if spr.payload.is_some() {
+                    spr.export_payload(name, dir)?;
+                } else if let SpriteState::Loaded { textures } = &spr.textures {
+                    match sprite_output.animation {
+                        _ if sprite_output.atlas == Some(AtlasLayout::Strip) => sprite_output.pack(std::iter::once((name, spr)), AtlasLayout::Strip)?.save_with(dir, name, options)?,
+                        Some(format) if textures.len() > 1 => spr.export_animation_with(dir.join(format!("{}.{}", name, format.extension())), format, sprite_output.fps(spr), options)?,
+                        _ => { spr.export_frames(name, dir, options)?; },
+                    }
+                }
+            }
+        },
+        Kind::Backgrounds => {
+            if let Some(bg) = data.bgnd.get().and_then(|bgnd| bgnd.backgrounds.get(name)) {
+                if let BackgroundState::Loaded { .. } = bg.texture {
+                    bg.export_texture(dir.join(format!("{}.png", name)), options)?;
+                }
+            }
+        },
+        Kind::Sounds => {
+            // external sounds (eg. streamed music) are copied from the game directory
+            let bytes = data.sound_data(name)?;
+            let format = AudioFormat::detect(&bytes);
+            fs::write(dir.join(format!("{}.{}", name, format.extension())), bytes)?;
+        },
+        Kind::Fonts => {
+            if let Some(fnt) = data.font.get().and_then(|font| font.fonts.get(name)) {
+                let font_dir = dir.join(name);
+                fs::create_dir_all(&font_dir)?;
+
+                fnt.export_bmfont_with(name, &font_dir, BmFontFormat::Text, options)?;
+                fnt.export_metrics_json(font_dir.join(format!("{}.json", name)))?;
+
+                fnt.export_glyphs(&font_dir, options)?;
+            }
+        },
+        Kind::All => unreachable!("write_asset is called for each kind"),
+    }
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,260 @@
+//! Exporting fonts as `BMFont` bitmap fonts (.fnt + atlas page) and JSON metrics.
+
+use std::{convert::TryFrom, fmt::Write as _, fs, path::Path};
+
+use image::GenericImageView;
+use serde_json::json;
+
+use crate::{ExportOptions, chunk::FontEntry};
+
+/// The kind of .fnt file written by `FontEntry::export_bmfont`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmFontFormat {
+    Text,
+    Xml,
+}
+
+/// A glyph as it's described in the .fnt file.
+struct BmChar<'a> {
+    id: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    xoffset: i32,
+    xadvance: i32,
+    kerning: &'a [(u16, i16)], // unscaled
+}
+
+/// Everything in the .fnt file except the page file name, in a stable order.
+struct BmFont<'a> {
+    size: i32,
+    line_height: u32,
+    base: u32,
+    scale_w: u32,
+    scale_h: u32,
+    chars: Vec<BmChar<'a>>,
+    kernings: Vec<(u32, u32, i32)>, // (first, second, amount)
+}
+
+impl<'a> BmFont<'a> {
+    /// The font's metrics, with every position and size multiplied by `scale` (for an atlas page that was scaled by as much).
+    fn new(font: &'a FontEntry, scale: u32) -> Self {
+        let scale = scale.max(1);
+        let iscale = i32::try_from(scale).unwrap_or(i32::MAX);
+
+        let mut ids = font.glyphs.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        let chars = ids.iter().map(|id| {
+            let gly = &font.glyphs[id];
+            BmChar {
+                id: u32::from(*id),
+                x: u32::from(gly.relative_x) * scale,
+                y: u32::from(gly.relative_y) * scale,
+                width: u32::from(gly.width) * scale,
+                height: u32::from(gly.height) * scale,
+                xoffset: i32::from(gly.offset) * iscale,
+                xadvance: i32::from(gly.shift) * iscale,
+                kerning: &gly.kerning,
+            }
+        }).collect::<Vec<_>>();
+
+        // GM stores kerning on the second character of the pair
+        let mut kernings = chars.iter()
+            .flat_map(|ch| ch.kerning.iter().map(move |(other, amount)| (u32::from(*other), ch.id, i32::from(*amount) * iscale)))
+            .collect::<Vec<_>>();
+        kernings.sort_unstable();
+
+        let (scale_w, scale_h) = font.texture.as_ref().map_or((0, 0), GenericImageView::dimensions);
+
+        let line_height = font.line_spacing();
+
+        Self {
+            size: font.em_size.round() as i32 * iscale,
+            line_height: line_height * scale,
+            base: font.ascender.unwrap_or(line_height) * scale,
+            scale_w: scale_w * scale,
+            scale_h: scale_h * scale,
+            chars,
+            kernings,
+        }
+    }
+}
+
+/// Makes a string safe to put between quotes in a text .fnt file, which has no escapes:
+/// double quotes become single quotes and line breaks become spaces.
+fn text_quote(s: &str) -> String {
+    s.replace('"', "'").replace(['\r', '\n'], " ")
+}
+
+/// Escapes a string for use in an XML attribute.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl FontEntry {
+    /// Builds the contents of a `BMFont` .fnt file for this font, referring to the atlas as `page_file`.
+    /// The GM scale is written as extra `scaleX`/`scaleY` attributes on the `info` line.
+    #[must_use]
+    pub fn bmfont_descriptor(&self, page_file: &str, format: BmFontFormat) -> String {
+        self.bmfont_descriptor_scaled(page_file, format, 1)
+    }
+
+    /// Like `bmfont_descriptor`, for an atlas page that was upscaled by `scale` (see `ExportOptions::scale`).
+    #[must_use]
+    pub fn bmfont_descriptor_scaled(&self, page_file: &str, format: BmFontFormat, scale: u32) -> String {
+        let fnt = BmFont::new(self, scale);
+        let mut out = String::new();
+
+        // writing to a String can't fail
+        match format {
+            BmFontFormat::Text => {
+                let _ = writeln!(out, "info face=\"{}\" size={} bold={} italic={} charset=\"\" unicode=1 stretchH=100 smooth={} aa=1 padding=0,0,0,0 spacing=0,0 scaleX={} scaleY={}",
+                    text_quote(&self.system_name), fnt.size, u8::from(self.bold), u8::from(self.italic), u8::from(self.antialiasing != 0), self.scale_x, self.scale_y);
+                let _ = writeln!(out, "common lineHeight={} base={} scaleW={} scaleH={} pages=1 packed=0", fnt.line_height, fnt.base, fnt.scale_w, fnt.scale_h);
+                let _ = writeln!(out, "page id=0 file=\"{}\"", text_quote(page_file));
+                let _ = writeln!(out, "chars count={}", fnt.chars.len());
+                for ch in &fnt.chars {
+                    let _ = writeln!(out, "char id={} x={} y={} width={} height={} xoffset={} yoffset=0 xadvance={} page=0 chnl=15",
+                        ch.id, ch.x, ch.y, ch.width, ch.height, ch.xoffset, ch.xadvance);
+                }
+                let _ = writeln!(out, "kernings count={}", fnt.kernings.len());
+                for (first, second, amount) in &fnt.kernings {
+                    let _ = writeln!(out, "kerning first={} second={} amount={}", first, second, amount);
+                }
+            },
+            BmFontFormat::Xml => {
+                let _ = writeln!(out, "<?xml version=\"1.0\"?>");
+                let _ = writeln!(out, "<font>");
+                let _ = writeln!(out, "  <info face=\"{}\" size=\"{}\" bold=\"{}\" italic=\"{}\" charset=\"\" unicode=\"1\" stretchH=\"100\" smooth=\"{}\" aa=\"1\" padding=\"0,0,0,0\" spacing=\"0,0\" scaleX=\"{}\" scaleY=\"{}\"/>",
+                    xml_escape(&self.system_name), fnt.size, u8::from(self.bold), u8::from(self.italic), u8::from(self.antialiasing != 0), self.scale_x, self.scale_y);
+                let _ = writeln!(out, "  <common lineHeight=\"{}\" base=\"{}\" scaleW=\"{}\" scaleH=\"{}\" pages=\"1\" packed=\"0\"/>", fnt.line_height, fnt.base, fnt.scale_w, fnt.scale_h);
+                let _ = writeln!(out, "  <pages>");
+                let _ = writeln!(out, "    <page id=\"0\" file=\"{}\"/>", xml_escape(page_file));
+                let _ = writeln!(out, "  </pages>");
+                let _ = writeln!(out, "  <chars count=\"{}\">", fnt.chars.len());
+                for ch in &fnt.chars {
+                    let _ = writeln!(out, "    <char id=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" xoffset=\"{}\" yoffset=\"0\" xadvance=\"{}\" page=\"0\" chnl=\"15\"/>",
+                        ch.id, ch.x, ch.y, ch.width, ch.height, ch.xoffset, ch.xadvance);
+                }
+                let _ = writeln!(out, "  </chars>");
+                let _ = writeln!(out, "  <kernings count=\"{}\">", fnt.kernings.len());
+                for (first, second, amount) in &fnt.kernings {
+                    let _ = writeln!(out, "    <kerning first=\"{}\" second=\"{}\" amount=\"{}\"/>", first, second, amount);
+                }
+                let _ = writeln!(out, "  </kernings>");
+                let _ = writeln!(out, "</font>");
+            },
+        }
+
+        out
+    }
+
+    /// Writes this font as a `BMFont` to `dir`: `{name}.fnt` and its atlas page `{name}_0.png`.
+    /// The font must be loaded first (see `DataWin::load_font`).
+    pub fn export_bmfont<P: AsRef<Path>>(&self, name: &str, dir: P, format: BmFontFormat) -> anyhow::Result<()> {
+        self.export_bmfont_with(name, dir, format, &ExportOptions::default())
+    }
+
+    /// The font's settings and glyph metrics as JSON, for tools that don't read .fnt files.
+    #[must_use]
+    pub fn metrics_json(&self) -> serde_json::Value {
+        let fnt = BmFont::new(self, 1);
+
+        json!({
+            "system_name": self.system_name,
+            "size": self.em_size,
+            "bold": self.bold,
+            "italic": self.italic,
+            "antialiasing": self.antialiasing,
+            "scale_x": self.scale_x,
+            "scale_y": self.scale_y,
+            "ascender_offset": self.ascender_offset,
+            "ascender": self.ascender,
+            "sdf_spread": self.sdf_spread,
+            "line_height": fnt.line_height,
+            "base": fnt.base,
+            "atlas": { "width": fnt.scale_w, "height": fnt.scale_h },
+            "glyphs": fnt.chars.iter().map(|ch| json!({
+                "char": std::char::from_u32(ch.id).map(String::from),
+                "code": ch.id,
+                "x": ch.x,
+                "y": ch.y,
+                "width": ch.width,
+                "height": ch.height,
+                "offset": ch.xoffset,
+                "shift": ch.xadvance,
+                "kerning": ch.kerning.iter().map(|(other, amount)| json!({ "other": other, "amount": amount })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Saves `metrics_json` to a file.
+    pub fn export_metrics_json<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.metrics_json())?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::DynamicImage;
+
+    use super::*;
+    use crate::chunk::Glyph;
+
+    fn font() -> FontEntry {
+        let mut font = FontEntry::with_glyphs(vec![Glyph::new('A', 0, 4, 6, 5), Glyph::new('V', 4, 5, 6, 6)]);
+        font.ascender = Some(5);
+        font.texture = Some(DynamicImage::new_rgba8(16, 8));
+        font.glyphs.get_mut(&'V').unwrap().kerning.push((u16::from(b'A'), -2));
+        font
+    }
+
+    #[test]
+    fn text_descriptor() {
+        let fnt = font().bmfont_descriptor("font_0.png", BmFontFormat::Text);
+        assert_eq!(fnt, "\
+info face=\"Arial\" size=12 bold=0 italic=0 charset=\"\" unicode=1 stretchH=100 smooth=1 aa=1 padding=0,0,0,0 spacing=0,0 scaleX=1 scaleY=1
+common lineHeight=6 base=5 scaleW=16 scaleH=8 pages=1 packed=0
+page id=0 file=\"font_0.png\"
+chars count=2
+char id=65 x=0 y=0 width=4 height=6 xoffset=0 yoffset=0 xadvance=5 page=0 chnl=15
+char id=86 x=4 y=0 width=5 height=6 xoffset=0 yoffset=0 xadvance=6 page=0 chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-2
+");
+    }
+
+    #[test]
+    fn text_descriptor_replaces_quotes_in_names() {
+        let mut font = font();
+        font.system_name = "Comic \"Sans\"\nMS".to_string();
+        let text = font.bmfont_descriptor("a\"b.png", BmFontFormat::Text);
+        assert!(text.starts_with("info face=\"Comic 'Sans' MS\" size=12 "), "{}", text);
+        assert!(text.contains("\npage id=0 file=\"a'b.png\"\n"), "{}", text);
+    }
+
+    #[test]
+    fn xml_descriptor_escapes_names() {
+        let mut font = font();
+        font.system_name = "Comic \"Sans\" & <co>".to_string();
+        let xml = font.bmfont_descriptor("a&b.png", BmFontFormat::Xml);
+        assert!(xml.starts_with("<?xml version=\"1.0\"?>\n<font>\n  <info face=\"Comic &quot;Sans&quot; &amp; &lt;co&gt;\" size=\"12\""), "{}", xml);
+        assert!(xml.contains("    <page id=\"0\" file=\"a&amp;b.png\"/>\n"), "{}", xml);
+        assert!(xml.contains("    <char id=\"86\" x=\"4\" y=\"0\" width=\"5\" height=\"6\" xoffset=\"0\" yoffset=\"0\" xadvance=\"6\" page=\"0\" chnl=\"15\"/>\n"), "{}", xml);
+        assert!(xml.contains("  <kernings count=\"1\">\n    <kerning first=\"65\" second=\"86\" amount=\"-2\"/>\n  </kernings>\n</font>\n"), "{}", xml);
+    }
+
+    #[test]
+    fn metrics_json_lists_glyphs_in_order() {
+        let json = font().metrics_json();
+        assert_eq!(json["atlas"], json!({ "width": 16, "height": 8 }));
+        assert_eq!((json["line_height"].as_u64(), json["base"].as_u64()), (Some(6), Some(5)));
+        assert_eq!(json["glyphs"][1], json!({
+            "char": "V", "code": 86, "x": 4, "y": 0, "width": 5, "height": 6, "offset": 0, "shift": 6,
+            "kerning": [{ "other": 65, "amount": -2 }],
+        }));
+    }
+}
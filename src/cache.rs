@@ -0,0 +1,309 @@
+use std::{collections::HashMap, hash::Hash, ops::Deref, path::Path, sync::{Arc, Mutex}};
+
+use image::DynamicImage;
+
+use crate::chunk::{PNGState, Txtr};
+
+/// The default memory budget for images decoded on demand (512 MiB).
+pub const DEFAULT_TEXTURE_CACHE_BUDGET: usize = 512 * 1024 * 1024;
+
+/// What an entry in the `TextureCache` is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum CacheKey {
+    Sheet(usize),
+    Sprite(String),
+    Background(String),
+    Font(String),
+}
+
+/// LRU cache of images that were decoded on demand: spritesheets (ie. not loaded with `load_spritesheets`),
+/// and the sprites/backgrounds/fonts loaded through `&self` (eg. `DataWin::sprite_frames`). They all share one memory budget.
+#[derive(Debug)]
+pub(crate) struct TextureCache {
+    state: Mutex<CacheState>,
+    decode_locks: DecodeLocks<CacheKey>,
+}
+
+/// Per-key locks held while something is being decoded, so parallel loaders wait for it instead of decoding the same thing again.
+/// A key's lock is removed once nobody is holding or waiting on it.
+#[derive(Debug)]
+pub(crate) struct DecodeLocks<K>(Mutex<HashMap<K, Arc<Mutex<()>>>>);
+
+impl<K> Default for DecodeLocks<K> {
+    fn default() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+}
+
+impl<K: Eq + Hash + Clone> DecodeLocks<K> {
+    /// Runs `f` while holding the lock for `key`.
+    pub fn with<T>(&self, key: &K, f: impl FnOnce() -> T) -> T {
+        let lock = Arc::clone(self.0.lock().unwrap().entry(key.clone()).or_default());
+        let result = {
+            let _guard = lock.lock().unwrap();
+            f()
+        };
+
+        // others only get the lock through the map, so if it's just the map and us, no one is waiting
+        let mut locks = self.0.lock().unwrap();
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(key);
+        }
+
+        result
+    }
+}
+
+#[derive(Debug)]
+struct CacheState {
+    budget: usize,
+    used: usize,
+    tick: u64,
+    entries: HashMap<CacheKey, CacheEntry>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    images: Vec<Arc<DynamicImage>>, // one for spritesheets and fonts, none for backgrounds without a texture
+    size: usize,
+    last_used: u64,
+}
+
+impl CacheState {
+    /// Evicts least recently used entries until we're under budget (or only `keep` is left).
+    fn evict(&mut self, keep: Option<&CacheKey>) {
+        while self.used > self.budget {
+            let lru = self.entries.iter()
+                .filter(|(key, _)| Some(*key) != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+
+            match lru.and_then(|key| self.entries.remove(&key)) {
+                Some(entry) => self.used -= entry.size,
+                None => break,
+            }
+        }
+    }
+}
+
+impl TextureCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                budget,
+                used: 0,
+                tick: 0,
+                entries: HashMap::new(),
+            }),
+            decode_locks: DecodeLocks::default(),
+        }
+    }
+
+    pub fn set_budget(&self, budget: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.budget = budget;
+        state.evict(None);
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.used = 0;
+    }
+
+    pub fn remove(&self, key: &CacheKey) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.remove(key) {
+            state.used -= entry.size;
+        }
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Vec<Arc<DynamicImage>>> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.get_mut(key).map(|entry| {
+            entry.last_used = tick;
+            entry.images.clone()
+        })
+    }
+
+    fn insert(&self, key: &CacheKey, images: Vec<Arc<DynamicImage>>) {
+        let size = images.iter().map(|image| image.as_bytes().len()).sum();
+
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let entry = CacheEntry {
+            images,
+            size,
+            last_used: state.tick,
+        };
+        if let Some(old) = state.entries.insert(key.clone(), entry) {
+            state.used -= old.size;
+        }
+        state.used += size;
+        state.evict(Some(key));
+    }
+
+    /// Gets `key`, or inserts the result of `f` if it isn't there yet. Each key is only decoded once, even when several threads
+    /// ask for it at the same time.
+    pub fn get_or_try_insert_with(&self, key: &CacheKey, f: impl FnOnce() -> anyhow::Result<Vec<Arc<DynamicImage>>>) -> anyhow::Result<Vec<Arc<DynamicImage>>> {
+        if let Some(images) = self.get(key) {
+            return Ok(images);
+        }
+
+        self.decode_locks.with(key, || {
+            // another thread might have decoded it while we were waiting
+            if let Some(images) = self.get(key) {
+                return Ok(images);
+            }
+
+            let images = f()?;
+            self.insert(key, images.clone());
+            Ok(images)
+        })
+    }
+}
+
+/// A spritesheet that is either fully loaded (`PNGState::Loaded`) or was decoded into the cache.
+pub(crate) enum SheetRef<'a> {
+    Loaded(&'a DynamicImage),
+    Cached(Arc<DynamicImage>),
+}
+
+impl Deref for SheetRef<'_> {
+    type Target = DynamicImage;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            SheetRef::Loaded(texture) => texture,
+            SheetRef::Cached(texture) => texture,
+        }
+    }
+}
+
+/// Everything needed to get at a spritesheet's texture, decoding it if needed.
+pub(crate) struct TextureSource<'a> {
+    pub txtr: &'a Txtr,
+    pub game_dir: Option<&'a Path>,
+    pub cache: &'a TextureCache,
+}
+
+impl TextureSource<'_> {
+    pub fn sheet(&self, data: &[u8], id: usize) -> anyhow::Result<SheetRef<'_>> {
+        let spr = self.txtr.spritesheets.get(id).ok_or_else(|| anyhow::anyhow!("Spritesheet {} does not exist!", id))?;
+
+        if let PNGState::Loaded { texture } = &spr.png {
+            return Ok(SheetRef::Loaded(texture));
+        }
+
+        let images = self.cache.get_or_try_insert_with(&CacheKey::Sheet(id), || {
+            Ok(vec![Arc::new(crate::DataWin::decode_spritesheet(spr, data, self.game_dir)?)])
+        })?;
+        Ok(SheetRef::Cached(Arc::clone(&images[0])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{Barrier, atomic::{AtomicBool, AtomicUsize, Ordering}}, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn decode_locks_are_exclusive_and_removed_when_done() {
+        let locks = DecodeLocks::default();
+        let inside = AtomicBool::new(false);
+        let runs = AtomicUsize::new(0);
+        let barrier = Barrier::new(8);
+
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    barrier.wait();
+                    locks.with(&7_usize, || {
+                        assert!(!inside.swap(true, Ordering::SeqCst), "two threads held the same lock");
+                        thread::sleep(Duration::from_millis(2));
+                        runs.fetch_add(1, Ordering::SeqCst);
+                        inside.store(false, Ordering::SeqCst);
+                    });
+                });
+            }
+        });
+
+        assert_eq!(runs.load(Ordering::SeqCst), 8);
+        assert!(locks.0.lock().unwrap().is_empty());
+    }
+
+    /// A 4x4 RGBA image, which is 64 bytes.
+    fn image() -> Arc<DynamicImage> {
+        Arc::new(DynamicImage::new_rgba8(4, 4))
+    }
+
+    #[test]
+    fn cached_images_are_decoded_once() {
+        let cache = TextureCache::new(DEFAULT_TEXTURE_CACHE_BUDGET);
+        let decodes = AtomicUsize::new(0);
+        let barrier = Barrier::new(8);
+
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    barrier.wait();
+                    let images = cache.get_or_try_insert_with(&CacheKey::Sprite("spr".to_string()), || {
+                        thread::sleep(Duration::from_millis(2));
+                        decodes.fetch_add(1, Ordering::SeqCst);
+                        Ok(vec![image(), image()])
+                    }).unwrap();
+                    assert_eq!(images.len(), 2);
+                });
+            }
+        });
+        assert_eq!(decodes.load(Ordering::SeqCst), 1);
+
+        // errors aren't cached
+        let bg = CacheKey::Background("bg".to_string());
+        assert!(cache.get_or_try_insert_with(&bg, || Err(anyhow::anyhow!("oops"))).is_err());
+        assert_eq!(cache.get_or_try_insert_with(&bg, || Ok(Vec::new())).unwrap().len(), 0);
+
+        let spr = CacheKey::Sprite("spr".to_string());
+        cache.remove(&spr);
+        assert_eq!(cache.state.lock().unwrap().used, 0);
+        assert_eq!(cache.get_or_try_insert_with(&spr, || Ok(vec![image()])).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used_entries() {
+        let cache = TextureCache::new(150);
+        cache.insert(&CacheKey::Sheet(0), vec![image()]);
+        cache.insert(&CacheKey::Sheet(1), vec![image()]);
+        cache.get(&CacheKey::Sheet(0));
+        cache.insert(&CacheKey::Sheet(2), vec![image()]);
+
+        assert!(cache.get(&CacheKey::Sheet(0)).is_some());
+        assert!(cache.get(&CacheKey::Sheet(1)).is_none());
+        assert!(cache.get(&CacheKey::Sheet(2)).is_some());
+        assert_eq!(cache.state.lock().unwrap().used, 128);
+    }
+
+    #[test]
+    fn sprites_backgrounds_and_fonts_share_the_budget() {
+        let cache = TextureCache::new(200);
+        cache.insert(&CacheKey::Sheet(0), vec![image()]);
+        cache.insert(&CacheKey::Font("fnt".to_string()), vec![image()]);
+        cache.insert(&CacheKey::Sprite("spr".to_string()), vec![image(), image()]);
+
+        // the sprite's 128 bytes push out the spritesheet
+        assert!(cache.get(&CacheKey::Sheet(0)).is_none());
+        assert!(cache.get(&CacheKey::Font("fnt".to_string())).is_some());
+        assert_eq!(cache.state.lock().unwrap().used, 192);
+
+        // lowering the budget evicts the least recently used entries first
+        cache.set_budget(100);
+        assert!(cache.get(&CacheKey::Sprite("spr".to_string())).is_none());
+        assert!(cache.get(&CacheKey::Font("fnt".to_string())).is_some());
+        cache.set_budget(0);
+        assert!(cache.get(&CacheKey::Font("fnt".to_string())).is_none());
+        assert_eq!(cache.state.lock().unwrap().used, 0);
+    }
+}
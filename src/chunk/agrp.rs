@@ -0,0 +1,127 @@
+use std::convert::TryInto;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::{Chunk, read_string_ptr};
+
+
+#[derive(Debug)]
+pub struct Agrp {
+    pub groups: Vec<AudioGroup>, // indexed by SoundEntry::group_id
+}
+
+#[derive(Debug)]
+pub struct AudioGroup {
+    pub name: String,
+    pub path: Option<String>, // GM 2024.14+, relative to the game directory
+}
+
+impl Agrp {
+    /// Gets a group by its id (`SoundEntry::group_id`).
+    #[must_use]
+    pub fn group(&self, id: i32) -> Option<&AudioGroup> {
+        let id: usize = id.try_into().ok()?;
+        self.groups.get(id)
+    }
+}
+
+impl AudioGroup {
+    /// The file this group's sounds are stored in, relative to the game directory.
+    /// Group 0 is the default group, which is stored in data.win itself.
+    #[must_use]
+    pub fn file_name(&self, id: i32) -> Option<String> {
+        match &self.path {
+            _ if id == 0 => None,
+            Some(path) if !path.is_empty() => Some(path.clone()),
+            _ => Some(format!("audiogroup{}.dat", id)),
+        }
+    }
+}
+
+impl Chunk for Agrp {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+        let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
+        let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
+
+        // 2024.14+ adds a path after the name, which we can only detect from the distance between entries
+        let has_path = matches!(entries_addrs.as_slice(), [first, second, ..] if second - first >= 8);
+
+        let mut groups = Vec::new();
+        for addr in entries_addrs {
+            buf.set_position(addr.try_into()?);
+
+            let name = read_string_ptr(buf)?;
+            let path = if has_path { Some(read_string_ptr(buf)?) } else { None };
+
+            groups.push(AudioGroup {
+                name,
+                path,
+            });
+        }
+
+        Ok(Agrp {
+            groups,
+        })
+    }
+
+    fn get_id() -> [u8; 4] {
+        *b"AGRP"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::chunk::testing::Writer;
+
+    /// An AGRP chunk with these groups, with paths (GM 2024.14+) if any are given.
+    fn agrp_chunk(groups: &[(&str, Option<&str>)]) -> Vec<u8> {
+        let mut w = Writer::default();
+        let entries = w.pointer_list(groups.len());
+        let mut strings = Vec::new();
+        for (entry, (name, path)) in entries.into_iter().zip(groups) {
+            w.point_here(entry);
+            strings.push((w.placeholder(), *name));
+            if let Some(path) = path {
+                strings.push((w.placeholder(), *path));
+            }
+        }
+        for (ptr, s) in strings {
+            w.point_here(ptr);
+            w.string(s);
+        }
+        w.buf
+    }
+
+    fn parse(chunk: &[u8]) -> Agrp {
+        Agrp::parse(&mut Cursor::new(chunk)).unwrap()
+    }
+
+    #[test]
+    fn parses_groups_with_and_without_paths() {
+        let agrp = parse(&agrp_chunk(&[("audiogroup_default", None), ("audiogroup_music", None)]));
+        assert_eq!(agrp.groups.iter().map(|group| (group.name.as_str(), group.path.clone())).collect::<Vec<_>>(), [("audiogroup_default", None), ("audiogroup_music", None)]);
+
+        let agrp = parse(&agrp_chunk(&[("audiogroup_default", Some("")), ("audiogroup_music", Some("music.dat"))]));
+        assert_eq!(agrp.groups[1].path.as_deref(), Some("music.dat"));
+    }
+
+    #[test]
+    fn groups_are_found_by_id() {
+        let agrp = parse(&agrp_chunk(&[("audiogroup_default", None), ("audiogroup_music", None)]));
+        assert_eq!(agrp.group(1).map(|group| group.name.as_str()), Some("audiogroup_music"));
+        assert!(agrp.group(2).is_none());
+        assert!(agrp.group(-1).is_none());
+    }
+
+    #[test]
+    fn file_names_default_to_the_group_id() {
+        let group = |path: Option<&str>| AudioGroup { name: "audiogroup_music".to_string(), path: path.map(str::to_string) };
+        assert_eq!(group(None).file_name(0), None);
+        assert_eq!(group(None).file_name(3).as_deref(), Some("audiogroup3.dat"));
+        assert_eq!(group(Some("")).file_name(3).as_deref(), Some("audiogroup3.dat"));
+        assert_eq!(group(Some("audio/music.dat")).file_name(3).as_deref(), Some("audio/music.dat"));
+    }
+}
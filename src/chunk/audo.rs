@@ -1,4 +1,4 @@
-use std::{convert::TryInto, io::Read};
+use std::convert::TryInto;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
@@ -7,25 +7,35 @@ use super::Chunk;
 
 #[derive(Debug)]
 pub struct Audo {
-    pub sounds: Vec<Vec<u8>>,
+    pub sounds: Vec<AudioEntry>,
+}
+
+/// Where an embedded audio file is in the data.win (or audiogroup file). The bytes aren't copied out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioEntry {
+    pub offset: usize,
+    pub len: usize,
 }
 
 impl Chunk for Audo {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
         let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
         let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
         let mut sounds = Vec::new();
         for addr in entries_addrs {
             buf.set_position(addr.try_into()?);
 
-            let length = buf.read_u32::<LittleEndian>()?;
-
-            let mut bytes = vec![0_u8; length.try_into()?];
-            buf.read_exact(&mut bytes)?;
+            let len: usize = buf.read_u32::<LittleEndian>()?.try_into()?;
+            let offset: usize = buf.position().try_into()?;
 
-            assert!(bytes.len() == length.try_into()?);
+            if offset + len > buf.get_ref().len() {
+                return Err(anyhow::anyhow!("Audio at {:#x} is {} bytes long, which goes past the end of the file!", offset, len));
+            }
 
-            sounds.push(bytes);
+            sounds.push(AudioEntry {
+                offset,
+                len,
+            });
         }
 
         Ok(Audo {
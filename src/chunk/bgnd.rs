@@ -37,7 +37,7 @@ pub enum BackgroundState {
 }
 
 impl Chunk for Bgnd {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
         let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
         let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
         let mut backgrounds = HashMap::new();
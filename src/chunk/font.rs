@@ -1,9 +1,9 @@
-use std::{collections::HashMap, convert::TryInto};
+use std::{collections::HashMap, convert::{TryFrom, TryInto}, fmt};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use image::DynamicImage;
 
-use super::{Chunk, read_string_ptr};
+use super::{Chunk, chunk_end, read_string_ptr};
 
 
 #[derive(Debug)]
@@ -18,33 +18,215 @@ pub struct FontEntry {
     pub bold: bool,
     pub italic: bool,
     pub range_start: u16,
-    pub charset: u8,
+    pub charset: Charset,
     pub antialiasing: u8,
     pub range_end: u32,
     pub tpag_addr: u32,
     pub scale_x: f32,
     pub scale_y: f32,
-    pub glyphs: HashMap<u16, Glyph>,
+    pub ascender_offset: i32, // used to be `_unknown`
+    pub ascender: Option<u32>, // GM 2022.2+
+    pub sdf_spread: Option<u32>, // GM 2023.2+, 0 unless it's an SDF font
+    pub line_height: Option<u32>, // GM 2023.6+
+    pub glyphs: HashMap<char, Glyph>,
+    pub unmapped_glyphs: HashMap<u16, Glyph>, // glyphs whose code isn't a character on its own (lone UTF-16 surrogates)
+    pub texture: Option<DynamicImage>, // the atlas with all the glyphs, set by `DataWin::load_fonts`
 }
 
 #[derive(Debug)]
 pub struct Glyph {
+    pub code: u16, // the UTF-16 code unit as stored, see `Glyph::character`
     pub relative_x: u16,
     pub relative_y: u16,
     pub width: u16,
     pub height: u16,
-    pub _unknown1: Vec<u8>,
+    pub shift: i16, // how far to move right after drawing this glyph
+    pub offset: i16, // added to the x position when drawing this glyph
+    pub kerning: Vec<(u16, i16)>, // (previous character, extra shift), GMS2+
     pub texture: Option<DynamicImage>,
 }
 
+/// A font's Windows character set (`LOGFONT::lfCharSet`), which is what the font was created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Ansi,
+    Default,
+    Symbol,
+    ShiftJis,
+    Hangul,
+    Johab,
+    Gb2312,
+    ChineseBig5,
+    Greek,
+    Turkish,
+    Vietnamese,
+    Hebrew,
+    Arabic,
+    Baltic,
+    Russian,
+    Thai,
+    EastEurope,
+    Oem,
+    Other(u8),
+}
+
+impl From<u8> for Charset {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => Charset::Ansi,
+            1 => Charset::Default,
+            2 => Charset::Symbol,
+            128 => Charset::ShiftJis,
+            129 => Charset::Hangul,
+            130 => Charset::Johab,
+            134 => Charset::Gb2312,
+            136 => Charset::ChineseBig5,
+            161 => Charset::Greek,
+            162 => Charset::Turkish,
+            163 => Charset::Vietnamese,
+            177 => Charset::Hebrew,
+            178 => Charset::Arabic,
+            186 => Charset::Baltic,
+            204 => Charset::Russian,
+            222 => Charset::Thai,
+            238 => Charset::EastEurope,
+            255 => Charset::Oem,
+            id => Charset::Other(id),
+        }
+    }
+}
+
+impl From<Charset> for u8 {
+    fn from(charset: Charset) -> Self {
+        match charset {
+            Charset::Ansi => 0,
+            Charset::Default => 1,
+            Charset::Symbol => 2,
+            Charset::ShiftJis => 128,
+            Charset::Hangul => 129,
+            Charset::Johab => 130,
+            Charset::Gb2312 => 134,
+            Charset::ChineseBig5 => 136,
+            Charset::Greek => 161,
+            Charset::Turkish => 162,
+            Charset::Vietnamese => 163,
+            Charset::Hebrew => 177,
+            Charset::Arabic => 178,
+            Charset::Baltic => 186,
+            Charset::Russian => 204,
+            Charset::Thai => 222,
+            Charset::EastEurope => 238,
+            Charset::Oem => 255,
+            Charset::Other(id) => id,
+        }
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Charset::Other(id) => write!(f, "charset {}", id),
+            charset => fmt::Debug::fmt(charset, f),
+        }
+    }
+}
+
+// what 0x80..=0x9F are in Windows-1252 (0 where it's undefined)
+const CP1252_HIGH: [u16; 32] = [
+    0x20AC, 0, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039, 0x0152, 0, 0x017D, 0,
+    0, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0, 0x017E, 0x0178,
+];
+
+impl Charset {
+    /// Reads a stored code as Windows-1252 for ANSI fonts, where 0x80-0x9F would be C1 control characters in UTF-16 (eg. 0x80 as '€').
+    /// Other codes (and other charsets) are returned as they are, like `Glyph::character`.
+    ///
+    /// This is only a guess at how fonts made from a byte range might be meant, so nothing uses it unless you ask: glyphs are keyed by their UTF-16 code.
+    #[must_use]
+    pub fn decode_windows_1252(self, code: u16) -> Option<char> {
+        if self == Charset::Ansi && (0x80..=0x9F).contains(&code) {
+            let mapped = CP1252_HIGH[usize::from(code - 0x80)];
+            if mapped != 0 {
+                return std::char::from_u32(mapped.into());
+            }
+        }
+
+        std::char::from_u32(code.into())
+    }
+}
+
+impl Glyph {
+    /// The character this glyph draws, from its UTF-16 code. Lone surrogates aren't characters on their own, so they return `None`.
+    #[must_use]
+    pub fn character(&self) -> Option<char> {
+        std::char::from_u32(self.code.into())
+    }
+}
+
+// the bytes from the start of a font entry up to and including `ascender_offset`
+const FONT_HEADER_SIZE: u64 = 44;
+const GLYPH_SIZE: u64 = 14;
+
+/// The parts of the font layout that changed between versions. Nothing in the chunk says which one it is, but it's the same for
+/// every font, so it's worked out from whichever fonts show it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FontFormat {
+    extra_fields: u64, // between `ascender_offset` and the glyph list (0-3)
+    kerning: bool, // GMS2 adds a kerning list after each glyph
+}
+
+impl FontFormat {
+    /// `font_addrs` are the chunk's font entries, which each end where the next one starts (or at `chunk_end`).
+    /// Leaves `buf` where it was.
+    fn detect(buf: &mut std::io::Cursor<&[u8]>, font_addrs: &[u64], chunk_end: u64) -> Self {
+        let start = buf.position();
+        let entry_end = |addr: u64| font_addrs.iter().copied().filter(|next| *next > addr).min().unwrap_or(chunk_end);
+
+        // the glyph list's first entry points right after the list, like the glyphs always do. Without glyphs, the list is
+        //   all that's left of the entry
+        let from_glyphs = font_addrs.iter().find_map(|&addr| (0..=3).find(|extra| {
+            let list_pos = addr + FONT_HEADER_SIZE + extra * 4;
+            buf.set_position(list_pos);
+            matches!((read_u64(buf), read_u64(buf)), (Some(ct), Some(first)) if ct > 0 && first == list_pos + 4 + ct * 4)
+        }));
+        let from_entry_end = || font_addrs.iter().find_map(|&addr| (0..=3).find(|extra| addr + FONT_HEADER_SIZE + extra * 4 + 4 == entry_end(addr)));
+        let extra_fields = from_glyphs.or_else(from_entry_end).unwrap_or(0);
+
+        // with kerning, glyphs are more than 14 bytes apart, or a lone glyph is followed by more than its own 14 bytes
+        let kerning = font_addrs.iter().find_map(|&addr| {
+            buf.set_position(addr + FONT_HEADER_SIZE + extra_fields * 4);
+            let ct = read_u64(buf)?;
+            let mut glyph_addrs = (0..ct.min(2)).map(|_| read_u64(buf)).collect::<Option<Vec<u64>>>()?;
+            glyph_addrs.sort_unstable();
+            match glyph_addrs.as_slice() {
+                [first, second] => Some(second - first > GLYPH_SIZE),
+                [only] => Some(entry_end(addr).saturating_sub(*only) > GLYPH_SIZE),
+                _ => None,
+            }
+        }).unwrap_or(false);
+
+        buf.set_position(start);
+        FontFormat { extra_fields, kerning }
+    }
+}
+
+/// Reads an i32 that has to be positive or zero, like counts and addresses.
+fn read_u64(buf: &mut std::io::Cursor<&[u8]>) -> Option<u64> {
+    buf.read_i32::<LittleEndian>().ok().and_then(|v| u64::try_from(v).ok())
+}
+
 impl Chunk for Font {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+        let chunk_end = chunk_end(buf);
         let f_entries_addr_ct = buf.read_i32::<LittleEndian>()?;
         let f_entries_addrs = (0..f_entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
         let mut fonts = HashMap::new();
 
-        for f_addr in f_entries_addrs {
-            buf.set_position(f_addr.try_into()?);
+        let font_addrs = f_entries_addrs.iter().map(|addr| u64::try_from(*addr)).collect::<Result<Vec<u64>, _>>()?;
+        let format = FontFormat::detect(buf, &font_addrs, chunk_end);
+
+        for f_addr in font_addrs {
+            buf.set_position(f_addr);
 
             let code_name = read_string_ptr(buf)?;
             let system_name = read_string_ptr(buf)?;
@@ -52,17 +234,24 @@ impl Chunk for Font {
             let bold = buf.read_u32::<LittleEndian>()? == 1;
             let italic = buf.read_u32::<LittleEndian>()? == 1;
             let range_start = buf.read_u16::<LittleEndian>()?;
-            let charset = buf.read_u8()?;
+            let charset = Charset::from(buf.read_u8()?);
             let antialiasing = buf.read_u8()?;
             let range_end = buf.read_u32::<LittleEndian>()?;
             let tpag_addr = buf.read_u32::<LittleEndian>()?;
             let scale_x = buf.read_f32::<LittleEndian>()?;
             let scale_y = buf.read_f32::<LittleEndian>()?;
-            let _unknown = buf.read_i32::<LittleEndian>()?;
+            let ascender_offset = buf.read_i32::<LittleEndian>()?;
+
+            // newer versions add up to 3 fields before the glyph list
+            let mut extra = (0..format.extra_fields).map(|_| buf.read_u32::<LittleEndian>().map(Some)).collect::<Result<Vec<Option<u32>>, std::io::Error>>()?;
+            extra.resize(3, None);
+            let (ascender, sdf_spread, line_height) = (extra[0], extra[1], extra[2]);
 
             let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
             let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
+
             let mut glyphs = HashMap::new();
+            let mut unmapped_glyphs = HashMap::new();
             for addr in entries_addrs {
                 buf.set_position(addr.try_into()?);
                 // println!("{}", buf.position());
@@ -71,17 +260,32 @@ impl Chunk for Font {
                 let relative_y = buf.read_u16::<LittleEndian>()?;
                 let width = buf.read_u16::<LittleEndian>()?;
                 let height = buf.read_u16::<LittleEndian>()?;
+                let shift = buf.read_i16::<LittleEndian>()?;
+                let offset = buf.read_i16::<LittleEndian>()?;
 
-                let unknown1 = (0..4).map(|_| buf.read_u8()).collect::<Result<Vec<u8>, std::io::Error>>()?;
+                let kerning = if format.kerning {
+                    let kerning_ct = buf.read_u16::<LittleEndian>()?;
+                    (0..kerning_ct).map(|_| Ok((buf.read_u16::<LittleEndian>()?, buf.read_i16::<LittleEndian>()?))).collect::<Result<Vec<(u16, i16)>, std::io::Error>>()?
+                } else {
+                    Vec::new()
+                };
 
-                glyphs.insert(character, Glyph {
+                let glyph = Glyph {
+                    code: character,
                     relative_x,
                     relative_y,
                     width,
                     height,
-                    _unknown1: unknown1,
+                    shift,
+                    offset,
+                    kerning,
                     texture: None,
-                });
+                };
+
+                match glyph.character() {
+                    Some(ch) => glyphs.insert(ch, glyph),
+                    None => unmapped_glyphs.insert(character, glyph),
+                };
             }
 
             fonts.insert(code_name, FontEntry {
@@ -96,7 +300,13 @@ impl Chunk for Font {
                 tpag_addr,
                 scale_x,
                 scale_y,
+                ascender_offset,
+                ascender,
+                sdf_spread,
+                line_height,
                 glyphs,
+                unmapped_glyphs,
+                texture: None,
             });
 
         }
@@ -109,4 +319,205 @@ impl Chunk for Font {
     fn get_id() -> [u8; 4] {
         *b"FONT"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl FontEntry {
+    /// An unloaded ANSI font with these glyphs, for tests.
+    pub(crate) fn with_glyphs(glyphs: Vec<Glyph>) -> Self {
+        FontEntry {
+            system_name: "Arial".to_string(),
+            em_size: 12.0,
+            bold: false,
+            italic: false,
+            range_start: 32,
+            charset: Charset::Ansi,
+            antialiasing: 1,
+            range_end: 127,
+            tpag_addr: 0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            ascender_offset: 0,
+            ascender: None,
+            sdf_spread: None,
+            line_height: None,
+            glyphs: glyphs.into_iter().map(|gly| (gly.character().unwrap(), gly)).collect(),
+            unmapped_glyphs: HashMap::new(),
+            texture: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Glyph {
+    /// A glyph without kerning at `relative_x`, for tests.
+    pub(crate) fn new(code: char, relative_x: u16, width: u16, height: u16, shift: i16) -> Self {
+        Glyph {
+            code: code as u16,
+            relative_x,
+            relative_y: 0,
+            width,
+            height,
+            shift,
+            offset: 0,
+            kerning: Vec::new(),
+            texture: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::chunk::testing::Writer;
+
+    type Glyphs<'a> = &'a [(u16, &'a [(u16, i16)])];
+
+    /// A FONT chunk with ANSI fonts named by their index, with `extra` fields after `ascender_offset` and glyphs with these codes
+    /// and kerning (which is written for every glyph if `kerning` is set, like GMS2 does).
+    fn fonts_chunk(extra: &[u32], kerning: bool, fonts: &[Glyphs]) -> Vec<u8> {
+        let mut w = Writer::default();
+        let font_ptrs = w.pointer_list(fonts.len());
+        let names = (0..fonts.len()).map(|i| w.string(&i.to_string())).collect::<Vec<_>>();
+
+        for ((font_ptr, name), glyphs) in font_ptrs.into_iter().zip(names).zip(fonts) {
+            w.point_here(font_ptr);
+            w.u32(name);
+            w.u32(0); // system name
+            w.f32(-12.0);
+            w.u32(0); // bold
+            w.u32(0); // italic
+            w.bytes(&[32, 0, 0, 1]); // range start, charset, antialiasing
+            w.u32(127);
+            w.u32(0); // tpag
+            w.f32(1.0);
+            w.f32(1.0);
+            w.i32(0); // ascender offset
+            for v in extra {
+                w.u32(*v);
+            }
+
+            let glyph_ptrs = w.pointer_list(glyphs.len());
+            for (glyph_ptr, (code, kerns)) in glyph_ptrs.into_iter().zip(*glyphs) {
+                w.point_here(glyph_ptr);
+                for v in [*code, 0, 0, 4, 4, 5, 0] {
+                    w.u16(v);
+                }
+                if kerning {
+                    w.u16(kerns.len() as u16);
+                    for (other, amount) in *kerns {
+                        w.u16(*other);
+                        w.bytes(&amount.to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        w.buf
+    }
+
+    /// A FONT chunk with one font, named "0".
+    fn font_chunk(extra: &[u32], kerning: bool, glyphs: Glyphs) -> Vec<u8> {
+        fonts_chunk(extra, kerning, &[glyphs])
+    }
+
+    fn parse_all(chunk: &[u8]) -> HashMap<String, FontEntry> {
+        Font::parse(&mut Cursor::new(chunk)).unwrap().fonts
+    }
+
+    fn parse(chunk: &[u8]) -> FontEntry {
+        parse_all(chunk).remove("0").unwrap()
+    }
+
+    #[test]
+    fn glyphs_are_keyed_by_their_utf16_code() {
+        let font = parse(&font_chunk(&[], false, &[(0x41, &[]), (0x80, &[]), (0x3042, &[]), (0xd800, &[])]));
+
+        let mut chars = font.glyphs.keys().copied().collect::<Vec<_>>();
+        chars.sort_unstable();
+        assert_eq!(chars, ['A', '\u{80}', 'あ']);
+        assert_eq!(font.unmapped_glyphs.keys().copied().collect::<Vec<_>>(), [0xd800]);
+        assert_eq!(font.covers("Aあい\n€"), ['い', '€']);
+    }
+
+    #[test]
+    fn windows_1252_is_only_used_for_ansi_fonts() {
+        assert_eq!(Charset::Ansi.decode_windows_1252(0x80), Some('€'));
+        assert_eq!(Charset::Ansi.decode_windows_1252(0x81), Some('\u{81}')); // undefined in Windows-1252
+        assert_eq!(Charset::Ansi.decode_windows_1252(0x41), Some('A'));
+        assert_eq!(Charset::ShiftJis.decode_windows_1252(0x80), Some('\u{80}'));
+        assert_eq!(Charset::Ansi.decode_windows_1252(0xdc00), None);
+    }
+
+    #[test]
+    fn charsets_round_trip_through_their_ids() {
+        for id in 0..=255 {
+            assert_eq!(u8::from(Charset::from(id)), id);
+        }
+        assert_eq!(Charset::from(128), Charset::ShiftJis);
+        assert_eq!(Charset::from(7).to_string(), "charset 7");
+    }
+
+    #[test]
+    fn extra_fields_are_counted_from_the_glyph_list() {
+        let glyphs: Glyphs = &[(0x41, &[]), (0x42, &[])];
+        for (extra, expected) in [
+            (&[][..], (None, None, None)),
+            (&[7], (Some(7), None, None)),
+            (&[7, 0], (Some(7), Some(0), None)),
+            (&[7, 0, 20], (Some(7), Some(0), Some(20))),
+        ] {
+            let font = parse(&font_chunk(extra, false, glyphs));
+            assert_eq!((font.ascender, font.sdf_spread, font.line_height), expected, "{} extra fields", extra.len());
+            assert_eq!(font.glyphs.len(), 2, "{} extra fields", extra.len());
+        }
+    }
+
+    #[test]
+    fn kerning_is_read_when_glyphs_are_more_than_14_bytes_apart() {
+        let font = parse(&font_chunk(&[7], true, &[(0x41, &[]), (0x56, &[(0x41, -2), (0x54, -1)])]));
+        assert!(font.glyphs[&'A'].kerning.is_empty());
+        assert_eq!(font.glyphs[&'V'].kerning, [(0x41, -2), (0x54, -1)]);
+
+        let gly = &font.glyphs[&'V'];
+        assert_eq!((gly.width, gly.height, gly.shift, gly.offset), (4, 4, 5, 0));
+
+        let font = parse(&font_chunk(&[7], false, &[(0x41, &[]), (0x56, &[])]));
+        assert!(font.glyphs.values().all(|gly| gly.kerning.is_empty()));
+    }
+
+    #[test]
+    fn single_glyph_fonts_parse() {
+        for kerning in [false, true] {
+            let font = parse(&font_chunk(&[7, 0, 20], kerning, &[(0x41, &[])]));
+            assert_eq!(font.line_height, Some(20));
+            assert_eq!(font.glyphs[&'A'].shift, 5);
+        }
+    }
+    #[test]
+    fn extra_fields_are_found_without_glyphs() {
+        for extra in [&[][..], &[7], &[7, 0], &[7, 0, 20]] {
+            let font = parse(&font_chunk(extra, true, &[]));
+            assert_eq!([font.ascender, font.sdf_spread, font.line_height].iter().flatten().count(), extra.len(), "{} extra fields", extra.len());
+            assert!(font.glyphs.is_empty());
+        }
+
+        // or from another font, when one has glyphs
+        let fonts = parse_all(&fonts_chunk(&[7, 0, 20], true, &[&[], &[(0x41, &[])], &[]]));
+        assert!(fonts.values().all(|font| font.line_height == Some(20)));
+        assert_eq!(fonts["1"].glyphs.len(), 1);
+    }
+
+    #[test]
+    fn kerning_is_read_for_single_glyph_fonts() {
+        let fonts = parse_all(&fonts_chunk(&[7], true, &[&[(0x41, &[]), (0x56, &[])], &[(0x56, &[(0x41, -2)])]]));
+        assert_eq!(fonts["1"].glyphs[&'V'].kerning, [(0x41, -2)]);
+
+        // a lone glyph is followed by its kerning list when there's nothing else to go by
+        let font = parse(&font_chunk(&[], true, &[(0x56, &[(0x41, -2), (0x54, -1)])]));
+        assert_eq!(font.glyphs[&'V'].kerning, [(0x41, -2), (0x54, -1)]);
+        assert_eq!(font.glyphs[&'V'].shift, 5);
+    }
+}
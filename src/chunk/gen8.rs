@@ -33,7 +33,7 @@ pub struct Gen8 {
 }
 
 impl Chunk for Gen8 {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
         let debug = buf.read_u8()?;
         let unknown1 = buf.read_i24::<LittleEndian>()?;
         let filename = read_string_ptr(buf)?;
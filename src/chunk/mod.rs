@@ -11,6 +11,11 @@ mod txtr;
 mod audo;
 mod font;
 mod bgnd;
+mod tgin;
+mod agrp;
+mod spine;
+#[cfg(test)]
+pub(crate) mod testing;
 use byteorder::{LittleEndian, ReadBytesExt};
 pub use gen8::*;
 pub use optn::*;
@@ -21,19 +26,33 @@ pub use txtr::*;
 pub use audo::*;
 pub use font::*;
 pub use bgnd::*;
+pub use tgin::*;
+pub use agrp::*;
+pub use spine::*;
 
 pub trait Chunk {
-    fn parse(buf: &mut Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized;
+    fn parse(buf: &mut Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized;
     fn get_id() -> [u8; 4];
 }
 
-fn read_string_ptr(buf: &mut Cursor<Vec<u8>>) -> Result<String, anyhow::Error> {
+/// Where the chunk ends, for a `buf` positioned at the start of its data (just after its length).
+fn chunk_end(buf: &Cursor<&[u8]>) -> u64 {
+    let data = buf.get_ref();
+    let pos = buf.position() as usize;
+
+    match pos.checked_sub(4).and_then(|len_pos| data.get(len_pos..pos)) {
+        Some(mut len) => (buf.position() + u64::from(len.read_u32::<LittleEndian>().unwrap_or(0))).min(data.len() as u64),
+        None => data.len() as u64,
+    }
+}
+
+fn read_string_ptr(buf: &mut Cursor<&[u8]>) -> Result<String, anyhow::Error> {
     read_string_at(buf.read_i32::<LittleEndian>()? as u64, buf)
 }
 
-fn read_string_at(pos: u64, buf: &mut Cursor<Vec<u8>>) -> Result<String, anyhow::Error> {
+fn read_string_at(pos: u64, buf: &mut Cursor<&[u8]>) -> Result<String, anyhow::Error> {
     if pos == 0 {
-        return Ok("".to_string());
+        return Ok(String::new());
     }
 
     let pos_before = buf.position();
@@ -47,7 +66,7 @@ fn read_string_at(pos: u64, buf: &mut Cursor<Vec<u8>>) -> Result<String, anyhow:
     str
 }
 
-fn read_string_raw(buf: &mut Cursor<Vec<u8>>) -> Result<String, anyhow::Error> {
+fn read_string_raw(buf: &mut Cursor<&[u8]>) -> Result<String, anyhow::Error> {
     let mut build = Vec::new();
 
     loop {
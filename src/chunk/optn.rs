@@ -13,7 +13,7 @@ pub struct Optn {
 }
 
 impl Chunk for Optn {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
         let unknown1 = (0..2).map(|_| buf.read_u32::<LittleEndian>()).collect::<Result<Vec<u32>, std::io::Error>>()?;
         let info = buf.read_u32::<LittleEndian>()?; // could parse more: InfoFlags
         let unknown2 = (0..0xC).map(|_| buf.read_u32::<LittleEndian>()).collect::<Result<Vec<u32>, std::io::Error>>()?;
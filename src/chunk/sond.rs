@@ -1,8 +1,8 @@
-use std::{collections::HashMap, convert::TryInto};
+use std::{collections::HashMap, convert::TryInto, io::{self, Cursor, Read, Seek, SeekFrom}, ops::Deref, path::Path, sync::Arc};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
-use super::{Chunk, read_string_ptr};
+use super::{Agrp, AudioEntry, AudioGroup, Chunk, read_string_ptr};
 
 
 #[derive(Debug)]
@@ -12,7 +12,7 @@ pub struct Sond {
 
 #[derive(Debug)]
 pub struct SoundEntry {
-    pub flags: u32, // SoundEntryFlags
+    pub flags: SoundFlags,
     pub type_: String,
     pub file: String,
     pub _unknown1: u32,
@@ -25,12 +25,370 @@ pub struct SoundEntry {
 
 #[derive(Debug)]
 pub enum AudioType {
-    Internal(Vec<u8>),
-    External,
+    Internal {
+        data: AudioData,
+        format: AudioFormat,
+    },
+    External {
+        path: String, // relative to the game directory, see `SoundEntry::external_file`
+    },
+}
+
+impl AudioType {
+    /// The audio file's bytes, if it's embedded.
+    #[must_use]
+    pub fn data(&self) -> Option<&[u8]> {
+        match self {
+            AudioType::Internal { data, .. } => Some(data.as_ref()),
+            AudioType::External { .. } => None,
+        }
+    }
+}
+
+/// An embedded audio file, which points into the (shared) data.win or audiogroup buffer instead of copying it.
+/// Derefs to the file's bytes.
+#[derive(Debug, Clone)]
+pub struct AudioData {
+    buf: Arc<Vec<u8>>,
+    offset: usize,
+    len: usize,
+}
+
+impl AudioData {
+    pub(crate) fn new(buf: Arc<Vec<u8>>, entry: AudioEntry) -> Self {
+        AudioData {
+            buf,
+            offset: entry.offset,
+            len: entry.len,
+        }
+    }
+}
+
+impl AsRef<[u8]> for AudioData {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf[self.offset..self.offset + self.len]
+    }
+}
+
+impl Deref for AudioData {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+/// How a sound is stored, set by its "Attributes" in GM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoundFlags(pub u32);
+
+impl SoundFlags {
+    pub const EMBEDDED: u32 = 0x1;
+    pub const COMPRESSED: u32 = 0x2;
+    pub const REGULAR: u32 = 0x64; // set on every sound made with GM:S 1.4+
+
+    /// The audio is in an AUDO chunk (data.win or an audiogroup file) instead of a separate file.
+    #[must_use]
+    pub fn is_embedded(self) -> bool {
+        self.0 & SoundFlags::EMBEDDED != 0
+    }
+
+    /// The audio is stored compressed (Ogg), rather than as a WAV.
+    #[must_use]
+    pub fn is_compressed(self) -> bool {
+        self.0 & SoundFlags::COMPRESSED != 0
+    }
+
+    /// The audio is embedded and compressed, and the game decompresses it when loading.
+    #[must_use]
+    pub fn is_decompressed_on_load(self) -> bool {
+        self.is_embedded() && self.is_compressed()
+    }
+
+    #[must_use]
+    pub fn is_regular(self) -> bool {
+        self.0 & SoundFlags::REGULAR == SoundFlags::REGULAR
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Ogg,
+    Wav,
+    Mp3,
+    Unknown,
+}
+
+impl AudioFormat {
+    /// Detects the format from the start of a file.
+    #[must_use]
+    pub fn detect(bytes: &[u8]) -> AudioFormat {
+        if bytes.starts_with(b"OggS") {
+            AudioFormat::Ogg
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WAVE") {
+            AudioFormat::Wav
+        } else if bytes.starts_with(b"ID3") || mp3_frame_header(bytes).is_some() {
+            AudioFormat::Mp3
+        } else {
+            AudioFormat::Unknown
+        }
+    }
+
+    /// The usual file extension for this format (without the dot).
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Unknown => "bin",
+        }
+    }
+}
+
+/// Metadata read from an audio file's headers, without decoding it.
+/// Any of the values can be missing if the headers are unusual or truncated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioInfo {
+    pub format: AudioFormat,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub duration: Option<f64>, // in seconds
+}
+
+// enough for any file's headers, and for the last Ogg page (which is at most 65307 bytes)
+const AUDIO_HEAD_LEN: u64 = 1 << 16;
+const OGG_TAIL_LEN: u64 = 1 << 17;
+
+impl AudioInfo {
+    #[must_use]
+    pub fn read(bytes: &[u8]) -> AudioInfo {
+        AudioInfo::from_parts(bytes, bytes, bytes.len() as u64)
+    }
+
+    /// Like `read`, but only reads the start of the file (and the end, for Ogg files), so it's cheap for big files.
+    pub fn read_from<R: Read + Seek>(mut reader: R) -> io::Result<AudioInfo> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let mut head = read_at(&mut reader, 0, AUDIO_HEAD_LEN)?;
+
+        // ID3 tags can hold pictures, so skip straight to the first MP3 frame
+        let mut start = 0;
+        if let Some(tag_len) = id3_len(&head) {
+            start = tag_len as u64;
+            head = read_at(&mut reader, start, AUDIO_HEAD_LEN)?;
+        }
+
+        let tail = match AudioFormat::detect(&head) {
+            AudioFormat::Ogg => read_at(&mut reader, len.saturating_sub(OGG_TAIL_LEN), OGG_TAIL_LEN)?,
+            _ => Vec::new(),
+        };
+
+        Ok(AudioInfo::from_parts(&head, &tail, len.saturating_sub(start)))
+    }
+
+    /// `head` is the start of the file, `tail` is its end (only used for Ogg) and `len` is its full length.
+    fn from_parts(head: &[u8], tail: &[u8], len: u64) -> AudioInfo {
+        let format = AudioFormat::detect(head);
+        let (sample_rate, channels, duration) = match format {
+            AudioFormat::Ogg => ogg_info(head, tail),
+            AudioFormat::Wav => Some(wav_info(head)),
+            AudioFormat::Mp3 => mp3_info(head, len),
+            AudioFormat::Unknown => None,
+        }.unwrap_or((None, None, None));
+
+        AudioInfo {
+            format,
+            sample_rate,
+            channels,
+            duration,
+        }
+    }
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, pos: u64, len: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(pos))?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+type HeaderInfo = (Option<u32>, Option<u16>, Option<f64>);
+
+fn u16_le(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The Vorbis identification header is the first packet (in `head`), and the last page's granule position (in `tail`) is the length in samples.
+#[allow(clippy::cast_precision_loss)]
+fn ogg_info(head: &[u8], tail: &[u8]) -> Option<HeaderInfo> {
+    // page header is 27 bytes + the segment table
+    let packet = 27 + usize::from(*head.get(26)?);
+    if head.get(packet..packet + 7)? != b"\x01vorbis" {
+        return None;
+    }
+    let channels = u16::from(*head.get(packet + 11)?);
+    let sample_rate = u32_le(head, packet + 12)?;
+
+    let last_page = tail.windows(4).rposition(|w| w == b"OggS")?;
+    let granule = u64::from_le_bytes(tail.get(last_page + 6..last_page + 14)?.try_into().ok()?);
+    let duration = (sample_rate > 0).then(|| granule as f64 / f64::from(sample_rate));
+
+    Some((Some(sample_rate), Some(channels), duration))
+}
+
+fn wav_info(bytes: &[u8]) -> HeaderInfo {
+    let (mut sample_rate, mut channels, mut block_align, mut data_len) = (None, None, None, None);
+
+    let mut pos = 12;
+    while let (Some(id), Some(len)) = (bytes.get(pos..pos + 4), u32_le(bytes, pos + 4)) {
+        let body = pos + 8;
+        match id {
+            b"fmt " => {
+                channels = u16_le(bytes, body + 2);
+                sample_rate = u32_le(bytes, body + 4);
+                block_align = u16_le(bytes, body + 12);
+            },
+            b"data" => data_len = Some(len),
+            _ => {},
+        }
+        // chunks are padded to an even length
+        pos = body + len as usize + (len as usize & 1);
+    }
+
+    let duration = match (data_len, block_align, sample_rate) {
+        (Some(len), Some(align), Some(rate)) if align > 0 && rate > 0 => Some(f64::from(len) / f64::from(align) / f64::from(rate)),
+        _ => None,
+    };
+
+    (sample_rate, channels, duration)
+}
+
+struct Mp3Frame {
+    sample_rate: u32,
+    channels: u16,
+    bitrate: u32, // in bits per second
+    samples_per_frame: u32,
+    side_info_len: usize, // for finding the Xing/Info header
+}
+
+fn mp3_frame_header(bytes: &[u8]) -> Option<Mp3Frame> {
+    const BITRATES_V1_L3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const BITRATES_V2_L3: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    let header = u32_be(bytes, 0)?;
+    if header >> 21 != 0x7ff {
+        return None;
+    }
+
+    // only layer III (which is what everything uses)
+    let version = (header >> 19) & 3; // 0 = 2.5, 2 = 2, 3 = 1
+    let layer = (header >> 17) & 3;
+    let bitrate_index = ((header >> 12) & 0xf) as usize;
+    let rate_index = ((header >> 10) & 3) as usize;
+    if version == 1 || layer != 1 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+
+    let base_rate = [44100, 48000, 32000][rate_index];
+    let mono = (header >> 6) & 3 == 3;
+    let (sample_rate, bitrate, samples_per_frame, side_info_len) = match version {
+        3 => (base_rate, BITRATES_V1_L3[bitrate_index], 1152, if mono { 17 } else { 32 }),
+        2 => (base_rate / 2, BITRATES_V2_L3[bitrate_index], 576, if mono { 9 } else { 17 }),
+        _ => (base_rate / 4, BITRATES_V2_L3[bitrate_index], 576, if mono { 9 } else { 17 }),
+    };
+
+    Some(Mp3Frame {
+        sample_rate,
+        channels: if mono { 1 } else { 2 },
+        bitrate: bitrate * 1000,
+        samples_per_frame,
+        side_info_len,
+    })
+}
+
+/// The length of the ID3 tag at the start of an MP3 file, if there is one.
+fn id3_len(bytes: &[u8]) -> Option<usize> {
+    if !bytes.starts_with(b"ID3") {
+        return None;
+    }
+    // the tag size is "syncsafe" (7 bits per byte)
+    let size = bytes.get(6..10)?.iter().fold(0_usize, |acc, b| (acc << 7) | usize::from(b & 0x7f));
+    Some(10 + size)
+}
+
+/// Reads the first frame header (after any ID3 tag), in a file of `len` bytes. The duration comes from the `Xing`/`Info` header if there is one,
+/// otherwise it's estimated from the bitrate.
+#[allow(clippy::cast_precision_loss)]
+fn mp3_info(bytes: &[u8], len: u64) -> Option<HeaderInfo> {
+    let start = id3_len(bytes).unwrap_or(0);
+    let frame_bytes = bytes.get(start..)?;
+    let frame = mp3_frame_header(frame_bytes)?;
+
+    let xing = 4 + frame.side_info_len;
+    let frames = match frame_bytes.get(xing..xing + 4) {
+        Some(b"Xing" | b"Info") if u32_be(frame_bytes, xing + 4)? & 1 != 0 => u32_be(frame_bytes, xing + 8),
+        _ => None,
+    };
+
+    let duration = match frames {
+        Some(frames) => f64::from(frames) * f64::from(frame.samples_per_frame) / f64::from(frame.sample_rate),
+        None => len.saturating_sub(start as u64) as f64 * 8.0 / f64::from(frame.bitrate),
+    };
+
+    Some((Some(frame.sample_rate), Some(frame.channels), Some(duration)))
+}
+
+impl SoundEntry {
+    /// Looks up the audio group this sound is in.
+    #[must_use]
+    pub fn group<'a>(&self, agrp: &'a Agrp) -> Option<&'a AudioGroup> {
+        agrp.group(self.group_id)
+    }
+
+    /// The file an external sound is stored in, relative to the game directory.
+    /// `file` usually doesn't have an extension, in which case it's taken from `type_` (eg. ".ogg").
+    #[must_use]
+    pub fn external_file(&self) -> String {
+        if Path::new(&self.file).extension().is_some() {
+            return self.file.clone();
+        }
+
+        match self.type_.as_str() {
+            "" => format!("{}.ogg", self.file),
+            ext if ext.starts_with('.') => format!("{}{}", self.file, ext),
+            ext => format!("{}.{}", self.file, ext),
+        }
+    }
+
+    /// Opens this sound's embedded audio for reading, straight from the backing buffer. It must be loaded first (see `DataWin::load_sound`).
+    pub fn open(&self) -> anyhow::Result<Cursor<AudioData>> {
+        match &self.audio_data {
+            Some(AudioType::Internal { data, .. }) => Ok(Cursor::new(data.clone())),
+            Some(AudioType::External { path }) => Err(anyhow::anyhow!("Sound is external ({}), use DataWin::sound_data to read it!", path)),
+            None => Err(anyhow::anyhow!("Sound must be loaded before opening it!")),
+        }
+    }
+
+    /// Reads the format/metadata of this sound's embedded audio. Returns `None` if it isn't loaded or is external.
+    #[must_use]
+    pub fn audio_info(&self) -> Option<AudioInfo> {
+        match &self.audio_data {
+            Some(AudioType::Internal { data, .. }) => Some(AudioInfo::read(data)),
+            _ => None,
+        }
+    }
 }
 
 impl Chunk for Sond {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
         let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
         let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
         let mut sounds = HashMap::new();
@@ -38,7 +396,7 @@ impl Chunk for Sond {
             buf.set_position(addr.try_into()?);
 
             let name = read_string_ptr(buf)?;
-            let flags = buf.read_u32::<LittleEndian>()?;
+            let flags = SoundFlags(buf.read_u32::<LittleEndian>()?);
             let type_ = read_string_ptr(buf)?;
             let file = read_string_ptr(buf)?;
             let unknown1 = buf.read_u32::<LittleEndian>()?;
@@ -68,4 +426,183 @@ impl Chunk for Sond {
     fn get_id() -> [u8; 4] {
         *b"SOND"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ogg_page(granule: u64, payload: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\0\0".to_vec();
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&[0; 12]); // serial, sequence, checksum
+        page.push(1);
+        page.push(payload.len() as u8);
+        page.extend_from_slice(payload);
+        page
+    }
+
+    /// A stereo 22050 Hz Ogg Vorbis file that's 3 seconds long, with `filler` bytes between the first and last page.
+    fn ogg(filler: usize) -> Vec<u8> {
+        let mut ident = b"\x01vorbis\0\0\0\0\x02".to_vec();
+        ident.extend_from_slice(&22050_u32.to_le_bytes());
+        let mut bytes = ogg_page(0, &ident);
+        bytes.resize(bytes.len() + filler, 0);
+        bytes.extend(ogg_page(22050 * 3, &[0; 8]));
+        bytes
+    }
+
+    /// An MPEG-1 layer III frame header (128 kbps, 44100 Hz, mono), followed by a `Xing` header if there's a frame count.
+    fn mp3_frame(frames: Option<u32>) -> Vec<u8> {
+        let mut bytes = vec![0xff, 0xfb, 0x90, 0xc4];
+        bytes.resize(4 + 17, 0);
+        if let Some(frames) = frames {
+            bytes.extend_from_slice(b"Xing\0\0\0\x01");
+            bytes.extend_from_slice(&frames.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn id3_tag(len: usize) -> Vec<u8> {
+        let mut bytes = b"ID3\x04\0\0".to_vec();
+        bytes.extend((0..4).rev().map(|i| (len >> (7 * i)) as u8 & 0x7f));
+        bytes.resize(10 + len, 0);
+        bytes
+    }
+
+    /// Counts how many bytes are read through it.
+    struct Counting<R> {
+        inner: R,
+        read: usize,
+    }
+
+    impl<R: Read> Read for Counting<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for Counting<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn ogg_info_reads_the_last_granule_position() {
+        let info = AudioInfo::read(&ogg(100));
+        assert_eq!(info, AudioInfo { format: AudioFormat::Ogg, sample_rate: Some(22050), channels: Some(2), duration: Some(3.0) });
+    }
+
+    #[test]
+    fn wav_info_skips_padded_chunks() {
+        let mut bytes = b"RIFF\0\0\0\0WAVELIST\x03\0\0\0abc\0fmt \x10\0\0\0\x01\0\x01\0".to_vec();
+        bytes.extend_from_slice(&8000_u32.to_le_bytes());
+        bytes.extend_from_slice(&16000_u32.to_le_bytes());
+        bytes.extend_from_slice(b"\x02\0\x10\0data");
+        bytes.extend_from_slice(&16000_u32.to_le_bytes());
+
+        // the data itself isn't needed, only its length
+        let info = AudioInfo::read(&bytes);
+        assert_eq!(info, AudioInfo { format: AudioFormat::Wav, sample_rate: Some(8000), channels: Some(1), duration: Some(1.0) });
+    }
+
+    #[test]
+    fn mp3_info_uses_the_xing_frame_count() {
+        let info = AudioInfo::read(&mp3_frame(Some(100)));
+        assert_eq!((info.format, info.sample_rate, info.channels), (AudioFormat::Mp3, Some(44100), Some(1)));
+        assert!((info.duration.unwrap() - 100.0 * 1152.0 / 44100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mp3_info_estimates_the_duration_after_the_id3_tag() {
+        let mut bytes = id3_tag(300);
+        bytes.extend(mp3_frame(None));
+        bytes.resize(310 + 16000, 0);
+
+        let info = AudioInfo::read(&bytes);
+        assert_eq!(info, AudioInfo { format: AudioFormat::Mp3, sample_rate: Some(44100), channels: Some(1), duration: Some(1.0) });
+    }
+
+    #[test]
+    fn read_from_only_reads_the_ends_of_big_files() {
+        let ogg = ogg(1 << 20);
+        let mut mp3 = id3_tag(200_000);
+        mp3.extend(mp3_frame(None));
+        mp3.resize(mp3.len() + 1_000_000, 0);
+
+        for bytes in [ogg, mp3] {
+            let mut reader = Counting { inner: Cursor::new(&bytes), read: 0 };
+            assert_eq!(AudioInfo::read_from(&mut reader).unwrap(), AudioInfo::read(&bytes));
+            assert!(reader.read < 300_000, "read {} of {} bytes", reader.read, bytes.len());
+        }
+    }
+
+    fn sound(type_: &str, file: &str) -> SoundEntry {
+        SoundEntry {
+            flags: SoundFlags(0x64),
+            type_: type_.to_string(),
+            file: file.to_string(),
+            _unknown1: 0,
+            volume: 1.0,
+            pitch: 1.0,
+            group_id: 0,
+            audio_id: -1,
+            audio_data: None,
+        }
+    }
+
+    #[test]
+    fn external_files_get_their_extension_from_the_type() {
+        assert_eq!(sound("", "mus_menu").external_file(), "mus_menu.ogg");
+        assert_eq!(sound(".mp3", "mus_menu").external_file(), "mus_menu.mp3");
+        assert_eq!(sound("wav", "mus_menu").external_file(), "mus_menu.wav");
+        assert_eq!(sound(".mp3", "mus/menu.ogg").external_file(), "mus/menu.ogg");
+    }
+
+    #[test]
+    fn sound_flags() {
+        let flags = |bits| {
+            let flags = SoundFlags(bits);
+            (flags.is_embedded(), flags.is_compressed(), flags.is_decompressed_on_load(), flags.is_regular())
+        };
+        assert_eq!(flags(0x64), (false, false, false, true));
+        assert_eq!(flags(0x65), (true, false, false, true));
+        assert_eq!(flags(0x66), (false, true, false, true));
+        assert_eq!(flags(0x67), (true, true, true, true));
+        assert_eq!(flags(0x1), (true, false, false, false));
+    }
+
+    #[test]
+    fn audio_data_points_into_the_shared_buffer() {
+        let buf = Arc::new(b"....RIFF\0\0\0\0WAVE....".to_vec());
+        let data = AudioData::new(Arc::clone(&buf), AudioEntry { offset: 4, len: 12 });
+        assert_eq!(&*data, b"RIFF\0\0\0\0WAVE");
+        assert_eq!(AudioFormat::detect(&data), AudioFormat::Wav);
+
+        let mut entry = sound("", "");
+        entry.audio_data = Some(AudioType::Internal { data: data.clone(), format: AudioFormat::Wav });
+        assert_eq!(Arc::strong_count(&buf), 3);
+
+        let mut reader = entry.open().unwrap();
+        reader.seek(SeekFrom::Start(8)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"WAVE");
+        assert_eq!(entry.audio_data.as_ref().and_then(AudioType::data), Some(&b"RIFF\0\0\0\0WAVE"[..]));
+    }
+
+    #[test]
+    fn only_loaded_embedded_sounds_can_be_opened() {
+        let mut entry = sound("", "mus_menu");
+        assert!(entry.open().is_err());
+        assert!(entry.audio_info().is_none());
+
+        entry.audio_data = Some(AudioType::External { path: "mus_menu.ogg".to_string() });
+        let err = entry.open().unwrap_err();
+        assert!(err.to_string().contains("use DataWin::sound_data"), "{}", err);
+        assert!(entry.audio_info().is_none());
+    }
+}
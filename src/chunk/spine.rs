@@ -0,0 +1,300 @@
+use std::{fs, io::{Cursor, Read}, path::{Path, PathBuf}};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::{SpriteEntry, SpriteType};
+
+/// The data of a sprite that isn't made of bitmap frames, as it's stored after the sprite's header.
+#[derive(Debug, Clone)]
+pub enum SpritePayload {
+    Swf { data: Vec<u8> }, // GM's converted (YYSWF) vector data
+    Spine { data: Vec<u8> }, // see `SpineData::parse`
+}
+
+impl SpritePayload {
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        match self {
+            SpritePayload::Swf { data } | SpritePayload::Spine { data } => data,
+        }
+    }
+}
+
+/// A Spine sprite's skeleton, atlas and texture pages.
+#[derive(Debug, Clone)]
+pub struct SpineData {
+    pub version: i32,
+    pub json: String,
+    pub atlas: String,
+    pub textures: Vec<SpineTexture>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpineTexture {
+    pub width: Option<i32>, // version 2+
+    pub height: Option<i32>, // version 2+
+    pub data: Vec<u8>, // a PNG file
+}
+
+impl SpineData {
+    /// Parses the payload of a Spine sprite. Only versions 1 and 2 are supported; for anything else, use the raw payload.
+    pub fn parse(payload: &[u8]) -> anyhow::Result<SpineData> {
+        let mut buf = Cursor::new(payload);
+
+        let version = buf.read_i32::<LittleEndian>()?;
+        if !(1..=2).contains(&version) {
+            return Err(anyhow::anyhow!("Spine data version {} isn't supported!", version));
+        }
+
+        let json_len = buf.read_u32::<LittleEndian>()?;
+        let atlas_len = buf.read_u32::<LittleEndian>()?;
+        // in version 1 there's only one texture, and this is its size
+        let texture_ct = buf.read_u32::<LittleEndian>()?;
+
+        let json = read_text(&mut buf, json_len).map_err(|e| anyhow::anyhow!("Failed to read Spine JSON: {}", e))?;
+        let atlas = read_text(&mut buf, atlas_len).map_err(|e| anyhow::anyhow!("Failed to read Spine atlas: {}", e))?;
+
+        let textures = if version == 1 {
+            vec![SpineTexture { width: None, height: None, data: read_bytes(&mut buf, texture_ct)? }]
+        } else {
+            (0..texture_ct).map(|_| {
+                let width = buf.read_i32::<LittleEndian>()?;
+                let height = buf.read_i32::<LittleEndian>()?;
+                let len = buf.read_u32::<LittleEndian>()?;
+                Ok(SpineTexture { width: Some(width), height: Some(height), data: read_bytes(&mut buf, len)? })
+            }).collect::<anyhow::Result<Vec<_>>>()?
+        };
+
+        Ok(SpineData {
+            version,
+            json,
+            atlas,
+            textures,
+        })
+    }
+}
+
+fn read_bytes(buf: &mut Cursor<&[u8]>, len: u32) -> anyhow::Result<Vec<u8>> {
+    let remaining = buf.get_ref().len() as u64 - buf.position().min(buf.get_ref().len() as u64);
+    if u64::from(len) > remaining {
+        return Err(anyhow::anyhow!("{} bytes go past the end of the sprite ({} left)", len, remaining));
+    }
+
+    let mut data = vec![0; len as usize];
+    buf.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Reads the JSON or atlas text, which GM stores lightly obfuscated.
+fn read_text(buf: &mut Cursor<&[u8]>, len: u32) -> anyhow::Result<String> {
+    let raw = read_bytes(buf, len)?;
+
+    // each byte has a running key subtracted from it
+    let mut key: u32 = 42;
+    let mut data = raw.iter().map(|byte| {
+        let decoded = byte.wrapping_sub(key as u8);
+        key = key.wrapping_mul(key.wrapping_sub(1));
+        decoded
+    }).collect::<Vec<_>>();
+
+    // some versions store the JSON as plain text
+    if raw.first() == Some(&b'{') && data.first() != Some(&b'{') {
+        data = raw;
+    }
+
+    String::from_utf8(data).map_err(|_| anyhow::anyhow!("it isn't valid text after decoding"))
+}
+
+impl SpriteEntry {
+    /// Errors if this sprite doesn't have bitmap frames, for loaders that need them.
+    pub(crate) fn ensure_bitmap(&self, name: &str) -> anyhow::Result<()> {
+        match self.sprite_type {
+            SpriteType::Normal => Ok(()),
+            SpriteType::Swf | SpriteType::Spine => Err(anyhow::anyhow!("Sprite \"{}\" is a {:?} sprite, which has no bitmap frames! Use its payload instead (see `SpriteEntry::export_payload`)", name, self.sprite_type)),
+            SpriteType::Other(id) => Err(anyhow::anyhow!("Sprite \"{}\" has an unknown type ({}), so its frames can't be loaded!", name, id)),
+        }
+    }
+
+    /// Parses this sprite's Spine data, see `SpineData::parse`.
+    pub fn spine(&self) -> anyhow::Result<SpineData> {
+        match &self.payload {
+            Some(SpritePayload::Spine { data }) => SpineData::parse(data),
+            _ => Err(anyhow::anyhow!("Sprite is a {:?} sprite, not a Spine one!", self.sprite_type)),
+        }
+    }
+
+    /// Writes an SWF or Spine sprite's data to files in `dir`, returning their paths:
+    /// `{name}.yyswf` for SWF sprites, and `{name}.json`, `{name}.atlas` and `{name}_N.png` for Spine sprites
+    /// (or `{name}.spine` with the raw payload if it can't be parsed).
+    pub fn export_payload<P: AsRef<Path>>(&self, name: &str, dir: P) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        let mut write = |file: String, data: &[u8]| -> anyhow::Result<()> {
+            let path = dir.join(file);
+            fs::write(&path, data)?;
+            files.push(path);
+            Ok(())
+        };
+
+        match &self.payload {
+            Some(SpritePayload::Swf { data }) => write(format!("{}.yyswf", name), data)?,
+            Some(SpritePayload::Spine { data }) => match SpineData::parse(data) {
+                Ok(spine) => {
+                    write(format!("{}.json", name), spine.json.as_bytes())?;
+                    write(format!("{}.atlas", name), spine.atlas.as_bytes())?;
+                    for (i, tex) in spine.textures.iter().enumerate() {
+                        write(format!("{}_{}.png", name, i), &tex.data)?;
+                    }
+                },
+                Err(e) => {
+                    debug!("couldn't parse Spine sprite {}, writing it raw: {}", name, e);
+                    write(format!("{}.spine", name), data)?;
+                },
+            },
+            None if self.sprite_type == SpriteType::Normal => return Err(anyhow::anyhow!("Sprite is a bitmap sprite, it has no payload to export!")),
+            None => return Err(anyhow::anyhow!("Sprite is an unknown type ({:?})!", self.sprite_type)),
+        }
+
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::testing::Writer;
+
+    /// Obfuscates text the way GM stores it.
+    fn obfuscate(text: &str) -> Vec<u8> {
+        let mut key: u32 = 42;
+        text.bytes().map(|byte| {
+            let encoded = byte.wrapping_add(key as u8);
+            key = key.wrapping_mul(key.wrapping_sub(1));
+            encoded
+        }).collect()
+    }
+
+    fn payload(version: i32, json: &[u8], atlas: &[u8], textures: &[(i32, i32, &[u8])]) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.u32(version as u32);
+        w.u32(json.len() as u32);
+        w.u32(atlas.len() as u32);
+        if version == 1 {
+            w.u32(textures[0].2.len() as u32);
+        } else {
+            w.u32(textures.len() as u32);
+        }
+        w.bytes(json);
+        w.bytes(atlas);
+        for (width, height, data) in textures {
+            if version != 1 {
+                w.u32(*width as u32);
+                w.u32(*height as u32);
+                w.u32(data.len() as u32);
+            }
+            w.bytes(data);
+        }
+        w.buf
+    }
+
+    fn spine_sprite(data: Vec<u8>) -> SpriteEntry {
+        let mut spr = SpriteEntry::with_frames(1, 1, Vec::new());
+        spr.sprite_type = SpriteType::Spine;
+        spr.payload = Some(SpritePayload::Spine { data });
+        spr
+    }
+
+    #[test]
+    fn version_1() {
+        let data = payload(1, &obfuscate("{\"bones\":[]}"), &obfuscate("page.png\nsize: 2,2"), &[(0, 0, b"png data")]);
+        let spine = SpineData::parse(&data).unwrap();
+
+        assert_eq!(spine.version, 1);
+        assert_eq!(spine.json, "{\"bones\":[]}");
+        assert_eq!(spine.atlas, "page.png\nsize: 2,2");
+        assert_eq!(spine.textures.iter().map(|tex| (tex.width, tex.height, tex.data.as_slice())).collect::<Vec<_>>(), [(None, None, &b"png data"[..])]);
+    }
+
+    #[test]
+    fn version_2() {
+        let data = payload(2, &obfuscate("{}"), &obfuscate("atlas"), &[(2, 2, b"first"), (4, 8, b"second")]);
+        let spine = SpineData::parse(&data).unwrap();
+
+        assert_eq!((spine.version, spine.json.as_str(), spine.atlas.as_str()), (2, "{}", "atlas"));
+        assert_eq!(spine.textures.iter().map(|tex| (tex.width, tex.height, tex.data.as_slice())).collect::<Vec<_>>(), [
+            (Some(2), Some(2), &b"first"[..]),
+            (Some(4), Some(8), &b"second"[..]),
+        ]);
+    }
+
+    #[test]
+    fn plain_json_is_kept() {
+        let data = payload(2, b"{\"skeleton\":{}}", &obfuscate("atlas"), &[]);
+        let spine = SpineData::parse(&data).unwrap();
+        assert_eq!(spine.json, "{\"skeleton\":{}}");
+        assert_eq!(spine.atlas, "atlas");
+        assert!(spine.textures.is_empty());
+    }
+
+    #[test]
+    fn invalid_payloads() {
+        for version in [0, 3, -1] {
+            let err = SpineData::parse(&payload(version, b"{}", b"", &[(0, 0, b"")])).unwrap_err();
+            assert_eq!(err.to_string(), format!("Spine data version {} isn't supported!", version));
+        }
+
+        // lengths that go past the end
+        let mut data = payload(2, &obfuscate("{}"), &obfuscate("atlas"), &[(2, 2, b"png")]);
+        data.truncate(data.len() - 1);
+        assert!(SpineData::parse(&data).unwrap_err().to_string().contains("past the end"));
+
+        let mut data = payload(2, &obfuscate("{}"), b"", &[]);
+        data[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(SpineData::parse(&data).unwrap_err().to_string().starts_with("Failed to read Spine JSON"));
+
+        assert!(SpineData::parse(&[2, 0]).is_err());
+    }
+
+    #[test]
+    fn only_spine_sprites_have_spine_data() {
+        let spr = spine_sprite(payload(2, &obfuscate("{}"), b"", &[]));
+        assert_eq!(spr.spine().unwrap().json, "{}");
+        assert!(spr.ensure_bitmap("spr").is_err());
+
+        let mut spr = SpriteEntry::with_frames(1, 1, Vec::new());
+        assert!(spr.spine().is_err());
+        assert!(spr.ensure_bitmap("spr").is_ok());
+
+        spr.sprite_type = SpriteType::Other(7);
+        assert!(spr.ensure_bitmap("spr").unwrap_err().to_string().contains("unknown type (7)"));
+    }
+
+    #[test]
+    fn export_payloads() {
+        let dir = std::env::temp_dir().join(format!("dr-extract-spine-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let names = |files: Vec<PathBuf>| files.iter().map(|path| path.strip_prefix(&dir).unwrap().to_str().unwrap().to_string()).collect::<Vec<_>>();
+
+        let spr = spine_sprite(payload(2, &obfuscate("{}"), &obfuscate("atlas"), &[(2, 2, b"first"), (4, 8, b"second")]));
+        assert_eq!(names(spr.export_payload("spr", &dir).unwrap()), ["spr.json", "spr.atlas", "spr_0.png", "spr_1.png"]);
+        assert_eq!(fs::read_to_string(dir.join("spr.atlas")).unwrap(), "atlas");
+        assert_eq!(fs::read(dir.join("spr_1.png")).unwrap(), b"second");
+
+        // data that can't be parsed is written as it is
+        let spr = spine_sprite(b"not spine".to_vec());
+        assert_eq!(names(spr.export_payload("raw", &dir).unwrap()), ["raw.spine"]);
+        assert_eq!(fs::read(dir.join("raw.spine")).unwrap(), b"not spine");
+
+        let mut spr = SpriteEntry::with_frames(1, 1, Vec::new());
+        spr.sprite_type = SpriteType::Swf;
+        spr.payload = Some(SpritePayload::Swf { data: b"yyswf".to_vec() });
+        assert_eq!(names(spr.export_payload("swf", &dir).unwrap()), ["swf.yyswf"]);
+
+        spr.payload = None;
+        spr.sprite_type = SpriteType::Normal;
+        assert!(spr.export_payload("bitmap", &dir).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
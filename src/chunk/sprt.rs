@@ -1,9 +1,9 @@
-use std::{collections::HashMap, convert::TryInto};
+use std::{collections::HashMap, convert::TryInto, io::Read};
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use image::DynamicImage;
+use image::{DynamicImage, GrayImage, Luma};
 
-use super::{Chunk, read_string_ptr};
+use super::{Chunk, SpritePayload, TextureEntry, chunk_end, read_string_ptr};
 
 
 #[derive(Debug)]
@@ -19,13 +19,100 @@ pub struct SpriteEntry {
     pub margin_right: i32,
     pub margin_bottom: i32,
     pub margin_top: i32,
-    pub _unknown1: Vec<u32>,
+    pub transparent: bool,
+    pub smooth: bool,
+    pub preload: bool,
     pub bbox_mode: u32,
-    pub sep_masks: u32,
+    pub sep_masks: u32, // 0 = rectangle, 1 = precise (uses `collision_masks`), 2 = rotated rectangle
     pub origin_x: i32,
     pub origin_y: i32,
+    pub version: Option<u32>, // None for the old (GMS1) format
+    pub sprite_type: SpriteType,
+    pub playback_speed: Option<f32>, // GMS2+
+    pub playback_speed_type: Option<PlaybackSpeedType>, // GMS2+
+    pub sequence_addr: Option<u32>, // version 2+, if the sprite has a sequence
+    pub nine_slice: Option<NineSlice>, // version 3+, if the sprite has nine-slice data
+    pub payload: Option<SpritePayload>, // the data of SWF and Spine sprites, which don't have textures or masks
     pub textures: SpriteState,
-    // unknown bytes to next object
+    pub frame_entries: Vec<TextureEntry>, // the TPAG entry for each loaded texture, which says where it goes on the sprite's canvas
+    pub collision_masks: Vec<CollisionMask>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteType {
+    Normal,
+    Swf,
+    Spine,
+    Other(u32),
+}
+
+impl From<u32> for SpriteType {
+    fn from(id: u32) -> Self {
+        match id {
+            0 => SpriteType::Normal,
+            1 => SpriteType::Swf,
+            2 => SpriteType::Spine,
+            id => SpriteType::Other(id),
+        }
+    }
+}
+
+/// What a sprite's `playback_speed` is measured in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackSpeedType {
+    FramesPerSecond,
+    FramesPerGameFrame,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NineSlice {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub enabled: bool,
+    pub tile_modes: [i32; 5], // left, top, right, bottom, center: 0 = stretch, 1 = repeat, 2 = mirror, 3 = blank repeat, 4 = hide
+}
+
+/// A precise collision mask, 1 bit per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionMask {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>, // rows are padded to whole bytes, most significant bit first
+}
+
+impl CollisionMask {
+    /// Whether the pixel at (`x`, `y`) is solid. Out of bounds pixels aren't.
+    #[must_use]
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+
+        let stride = (self.width as usize).div_ceil(8);
+        let byte = self.data.get(y as usize * stride + x as usize / 8).copied().unwrap_or(0);
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    /// The mask as a black (empty) and white (solid) image.
+    #[must_use]
+    pub fn to_image(&self) -> GrayImage {
+        GrayImage::from_fn(self.width, self.height, |x, y| Luma([if self.get(x, y) { 255 } else { 0 }]))
+    }
+}
+
+impl SpriteEntry {
+    /// The sprite's animation speed in frames per second, for a game running at `game_fps`.
+    /// Sprites from before GMS2 don't have a speed, and play one frame per game frame.
+    #[must_use]
+    pub fn playback_fps(&self, game_fps: f32) -> f32 {
+        match (self.playback_speed, self.playback_speed_type) {
+            (Some(speed), Some(PlaybackSpeedType::FramesPerSecond)) => speed,
+            (Some(speed), Some(PlaybackSpeedType::FramesPerGameFrame)) => speed * game_fps,
+            _ => game_fps,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -39,12 +126,62 @@ pub enum SpriteState {
     },
 }
 
+/// Reads `count` masks at the current position. Newer versions (GM 2024.6+) only store the bounding box of each mask
+/// instead of the whole sprite, which we can only tell by whether the full size would go past `end`.
+fn read_masks(buf: &mut std::io::Cursor<&[u8]>, spr: (i32, i32), bbox: (i32, i32), end: u64) -> anyhow::Result<Vec<CollisionMask>> {
+    let count = buf.read_u32::<LittleEndian>()?;
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mask_len = |(width, height): (u32, u32)| u64::from(width).div_ceil(8) * u64::from(height);
+    let dims = |(width, height): (i32, i32)| (width.max(0) as u32, height.max(0) as u32);
+    let full = dims(spr);
+    let (width, height) = if buf.position() + mask_len(full) * u64::from(count) <= end { full } else { dims(bbox) };
+
+    let len = mask_len((width, height)) as usize;
+    let mut masks = Vec::new();
+    for _ in 0..count {
+        let mut data = vec![0; len];
+        buf.read_exact(&mut data)?;
+        masks.push(CollisionMask { width, height, data });
+    }
+
+    // the masks are padded to 4 bytes all together
+    let total = len * count as usize;
+    buf.set_position(buf.position() + ((4 - total % 4) % 4) as u64);
+
+    Ok(masks)
+}
+
+fn read_nine_slice(buf: &mut std::io::Cursor<&[u8]>, addr: u32) -> anyhow::Result<NineSlice> {
+    buf.set_position(addr.into());
+
+    let left = buf.read_i32::<LittleEndian>()?;
+    let top = buf.read_i32::<LittleEndian>()?;
+    let right = buf.read_i32::<LittleEndian>()?;
+    let bottom = buf.read_i32::<LittleEndian>()?;
+    let enabled = buf.read_u32::<LittleEndian>()? != 0;
+    let mut tile_modes = [0; 5];
+    buf.read_i32_into::<LittleEndian>(&mut tile_modes)?;
+
+    Ok(NineSlice {
+        left,
+        top,
+        right,
+        bottom,
+        enabled,
+        tile_modes,
+    })
+}
+
 impl Chunk for Sprt {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+        let chunk_end = chunk_end(buf);
         let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
         let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
         let mut sprites = HashMap::new();
-        for addr in entries_addrs {
+        for &addr in &entries_addrs {
             buf.set_position(addr.try_into()?);
             // println!("{}", buf.position());
 
@@ -55,19 +192,71 @@ impl Chunk for Sprt {
             let margin_right = buf.read_i32::<LittleEndian>()?;
             let margin_bottom = buf.read_i32::<LittleEndian>()?;
             let margin_top = buf.read_i32::<LittleEndian>()?;
-            let unknown1 = (0..3).map(|_| buf.read_u32::<LittleEndian>()).collect::<Result<Vec<u32>, std::io::Error>>()?;
+            let transparent = buf.read_u32::<LittleEndian>()? != 0;
+            let smooth = buf.read_u32::<LittleEndian>()? != 0;
+            let preload = buf.read_u32::<LittleEndian>()? != 0;
             let bbox_mode = buf.read_u32::<LittleEndian>()?;
             let sep_masks = buf.read_u32::<LittleEndian>()?;
             let origin_x = buf.read_i32::<LittleEndian>()?;
             let origin_y = buf.read_i32::<LittleEndian>()?;
-            let _unknown2 = (0..7).map(|_| buf.read_u32::<LittleEndian>()).collect::<Result<Vec<u32>, std::io::Error>>()?;
-            // let _unknown2 = buf.read_i32::<LittleEndian>()?;
-            // let _unknown3 = buf.read_u32::<LittleEndian>()?;
-            // let _unknown4 = buf.read_u32::<LittleEndian>()?;
-            // let _unknown5 = buf.read_f32::<LittleEndian>()?;
-            // let _unknown6 = buf.read_u32::<LittleEndian>()?;
-            let texture_count = buf.read_i32::<LittleEndian>()?;
-            let texture_addresses = (0..texture_count).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
+
+            let mut version = None;
+            let mut sprite_type = SpriteType::Normal;
+            let mut playback_speed = None;
+            let mut playback_speed_type = None;
+            let mut sequence_addr = None;
+            let mut nine_slice_addr = None;
+
+            // the old format goes straight to the texture count, the new one starts with -1
+            let mut texture_count = buf.read_i32::<LittleEndian>()?;
+            if texture_count == -1 {
+                let v = buf.read_u32::<LittleEndian>()?;
+                version = Some(v);
+                sprite_type = SpriteType::from(buf.read_u32::<LittleEndian>()?);
+
+                // GMS2 adds the speed, but GM:S 1.4 can also write version 1. A texture count read as a float is tiny
+                //   (a subnormal), while a speed isn't, so that tells them apart
+                let next = buf.read_u32::<LittleEndian>()?;
+                buf.set_position(buf.position() - 4);
+                if v >= 2 || f32::from_bits(next).is_normal() {
+                    playback_speed = Some(buf.read_f32::<LittleEndian>()?);
+                    playback_speed_type = Some(if buf.read_u32::<LittleEndian>()? == 0 { PlaybackSpeedType::FramesPerSecond } else { PlaybackSpeedType::FramesPerGameFrame });
+                }
+                if v >= 2 {
+                    sequence_addr = Some(buf.read_u32::<LittleEndian>()?).filter(|addr| *addr != 0);
+                }
+                if v >= 3 {
+                    nine_slice_addr = Some(buf.read_u32::<LittleEndian>()?).filter(|addr| *addr != 0);
+                }
+
+                texture_count = if sprite_type == SpriteType::Normal { buf.read_i32::<LittleEndian>()? } else { 0 };
+            }
+
+            // the rest of the entry ends where the next thing starts
+            let next_entry = entries_addrs.iter().map(|addr| *addr as u64).filter(|next| *next > addr as u64).min();
+            let end = [next_entry, sequence_addr.map(u64::from), nine_slice_addr.map(u64::from)].iter().flatten()
+                .copied().filter(|next| *next > buf.position()).min().unwrap_or(chunk_end);
+
+            let mut payload = None;
+            let (texture_addresses, collision_masks) = match sprite_type {
+                SpriteType::Normal => {
+                    let texture_addresses = (0..texture_count).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
+                    let bbox = (margin_right - margin_left + 1, margin_bottom - margin_top + 1);
+                    let collision_masks = read_masks(buf, (width, height), bbox, end)?;
+
+                    (texture_addresses, collision_masks)
+                },
+                SpriteType::Swf | SpriteType::Spine => {
+                    let start = buf.position() as usize;
+                    let data = buf.get_ref().get(start..end as usize).ok_or_else(|| anyhow::anyhow!("Sprite \"{}\" goes past the end of the file!", name))?.to_vec();
+                    payload = Some(if sprite_type == SpriteType::Swf { SpritePayload::Swf { data } } else { SpritePayload::Spine { data } });
+
+                    (Vec::new(), Vec::new())
+                },
+                SpriteType::Other(_) => (Vec::new(), Vec::new()),
+            };
+
+            let nine_slice = nine_slice_addr.map(|addr| read_nine_slice(buf, addr)).transpose()?;
 
             sprites.insert(name, SpriteEntry {
                 width,
@@ -76,15 +265,26 @@ impl Chunk for Sprt {
                 margin_right,
                 margin_bottom,
                 margin_top,
-                _unknown1: unknown1,
+                transparent,
+                smooth,
+                preload,
                 bbox_mode,
                 sep_masks,
                 origin_x,
                 origin_y,
+                version,
+                sprite_type,
+                playback_speed,
+                playback_speed_type,
+                sequence_addr,
+                nine_slice,
+                payload,
                 textures: SpriteState::Unloaded {
                     texture_count,
                     texture_addresses,
                 },
+                frame_entries: Vec::new(),
+                collision_masks,
             });
         }
 
@@ -96,4 +296,218 @@ impl Chunk for Sprt {
     fn get_id() -> [u8; 4] {
         *b"SPRT"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl SpriteEntry {
+    /// A loaded `width`x`height` bitmap sprite with these frames, for tests.
+    pub(crate) fn with_frames(width: i32, height: i32, frames: Vec<(DynamicImage, TextureEntry)>) -> Self {
+        let (textures, frame_entries) = frames.into_iter().unzip();
+        SpriteEntry {
+            width,
+            height,
+            margin_left: 0,
+            margin_right: width - 1,
+            margin_bottom: height - 1,
+            margin_top: 0,
+            transparent: true,
+            smooth: false,
+            preload: false,
+            bbox_mode: 0,
+            sep_masks: 0,
+            origin_x: 0,
+            origin_y: 0,
+            version: None,
+            sprite_type: SpriteType::Normal,
+            playback_speed: None,
+            playback_speed_type: None,
+            sequence_addr: None,
+            nine_slice: None,
+            payload: None,
+            textures: SpriteState::Loaded { textures },
+            frame_entries,
+            collision_masks: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)] // the speeds are stored exactly
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::chunk::testing::Writer;
+
+    /// A SPRT chunk with one `width`x`height` sprite named "spr", with its bounding box as (left, right, bottom, top),
+    /// an origin of (2, 3), and `rest` written after the origin.
+    fn sprt_chunk(size: (i32, i32), bbox: [i32; 4], rest: impl FnOnce(&mut Writer)) -> Vec<u8> {
+        let mut w = Writer::default();
+        let entries = w.pointer_list(1);
+        let name = w.string("spr");
+        w.point_here(entries[0]);
+        w.u32(name);
+        for v in [size.0, size.1, bbox[0], bbox[1], bbox[2], bbox[3], 1, 0, 0, 0, 1, 2, 3] {
+            w.u32(v as u32);
+        }
+        rest(&mut w);
+        w.buf
+    }
+
+    /// The GMS2 header up to the sequence address: version, type and speed.
+    fn gms2_header(w: &mut Writer, version: u32, sprite_type: u32, speed: f32, per_game_frame: bool) {
+        w.u32(-1_i32 as u32);
+        w.u32(version);
+        w.u32(sprite_type);
+        w.u32(speed.to_bits());
+        w.u32(per_game_frame.into());
+    }
+
+    fn parse(chunk: &[u8]) -> SpriteEntry {
+        Sprt::parse(&mut Cursor::new(chunk)).unwrap().sprites.remove("spr").unwrap()
+    }
+
+    fn texture_addresses(spr: &SpriteEntry) -> (i32, Vec<i32>) {
+        match &spr.textures {
+            SpriteState::Unloaded { texture_count, texture_addresses } => (*texture_count, texture_addresses.clone()),
+            SpriteState::Loaded { .. } => panic!("sprite is loaded"),
+        }
+    }
+
+    #[test]
+    fn old_format() {
+        let spr = parse(&sprt_chunk((4, 2), [0, 3, 1, 0], |w| {
+            w.u32(2);
+            w.u32(100);
+            w.u32(200);
+            w.u32(1);
+            w.bytes(&[0b1010_0000, 0b0101_0000, 0, 0]);
+        }));
+
+        assert_eq!((spr.width, spr.height, spr.origin_x, spr.origin_y, spr.sep_masks), (4, 2, 2, 3, 1));
+        assert!(spr.transparent && spr.nine_slice.is_none());
+        assert_eq!((spr.version, spr.sprite_type, spr.playback_speed), (None, SpriteType::Normal, None));
+        assert_eq!(texture_addresses(&spr), (2, vec![100, 200]));
+        assert_eq!(spr.playback_fps(30.0), 30.0);
+
+        let mask = &spr.collision_masks[0];
+        assert_eq!((mask.width, mask.height), (4, 2));
+        assert!(mask.get(0, 0) && !mask.get(1, 0) && mask.get(2, 0));
+        assert!(!mask.get(0, 1) && mask.get(1, 1) && mask.get(3, 1));
+        assert!(!mask.get(4, 0) && !mask.get(0, 2));
+        assert_eq!(mask.to_image().get_pixel(1, 1), &Luma([255]));
+    }
+
+    #[test]
+    fn version_1_with_a_speed() {
+        let spr = parse(&sprt_chunk((4, 2), [0, 3, 1, 0], |w| {
+            gms2_header(w, 1, 0, 0.5, true);
+            w.u32(1);
+            w.u32(100);
+            w.u32(0);
+        }));
+
+        assert_eq!(spr.version, Some(1));
+        assert_eq!((spr.playback_speed, spr.playback_speed_type), (Some(0.5), Some(PlaybackSpeedType::FramesPerGameFrame)));
+        assert_eq!(spr.playback_fps(30.0), 15.0);
+        assert_eq!(texture_addresses(&spr), (1, vec![100]));
+    }
+
+    #[test]
+    fn version_1_without_a_speed() {
+        // GM:S 1.4 goes straight to the texture count, which is a subnormal when read as a float
+        let spr = parse(&sprt_chunk((4, 2), [0, 3, 1, 0], |w| {
+            w.u32(-1_i32 as u32);
+            w.u32(1);
+            w.u32(0);
+            w.u32(1);
+            w.u32(100);
+            w.u32(0);
+        }));
+
+        assert_eq!((spr.version, spr.playback_speed, spr.playback_speed_type), (Some(1), None, None));
+        assert_eq!(texture_addresses(&spr), (1, vec![100]));
+        assert!(spr.collision_masks.is_empty());
+    }
+
+    #[test]
+    fn masks_cover_the_whole_sprite_if_they_fit() {
+        let spr = parse(&sprt_chunk((16, 4), [2, 5, 2, 1], |w| {
+            w.u32(0);
+            w.u32(1);
+            w.bytes(&[0xff; 8]);
+        }));
+
+        let mask = &spr.collision_masks[0];
+        assert_eq!((mask.width, mask.height, mask.data.len()), (16, 4, 8));
+    }
+
+    #[test]
+    fn masks_only_cover_the_bounding_box_in_newer_versions() {
+        // two 4x2 masks, where two 16x4 ones wouldn't fit
+        let spr = parse(&sprt_chunk((16, 4), [2, 5, 2, 1], |w| {
+            w.u32(0);
+            w.u32(2);
+            w.bytes(&[0xf0, 0x80, 0x10, 0xf0]);
+        }));
+
+        assert_eq!(spr.collision_masks.len(), 2);
+        assert_eq!(spr.collision_masks.iter().map(|mask| (mask.width, mask.height)).collect::<Vec<_>>(), [(4, 2), (4, 2)]);
+        assert!(spr.collision_masks[1].get(3, 0) && !spr.collision_masks[1].get(0, 0));
+    }
+
+    #[test]
+    fn version_3_with_nine_slice() {
+        let spr = parse(&sprt_chunk((16, 4), [2, 5, 2, 1], |w| {
+            gms2_header(w, 3, 0, 12.0, false);
+            w.u32(0);
+            let nine_slice = w.placeholder();
+            w.u32(1);
+            w.u32(100);
+            w.u32(1);
+            w.bytes(&[0xf0, 0xf0, 0, 0]);
+
+            // the masks stop at the nine-slice data, even though a full size mask would fit before the chunk ends
+            w.point_here(nine_slice);
+            for v in [1, 2, 3, 4, 1, 0, 1, 2, 3, 4] {
+                w.u32(v);
+            }
+        }));
+
+        assert_eq!(spr.version, Some(3));
+        assert_eq!((spr.playback_speed, spr.playback_speed_type), (Some(12.0), Some(PlaybackSpeedType::FramesPerSecond)));
+        assert_eq!(spr.playback_fps(30.0), 12.0);
+        assert_eq!(spr.sequence_addr, None);
+        assert_eq!(texture_addresses(&spr), (1, vec![100]));
+        assert_eq!(spr.nine_slice, Some(NineSlice { left: 1, top: 2, right: 3, bottom: 4, enabled: true, tile_modes: [0, 1, 2, 3, 4] }));
+        assert_eq!(spr.collision_masks.iter().map(|mask| (mask.width, mask.height)).collect::<Vec<_>>(), [(4, 2)]);
+    }
+
+    #[test]
+    fn swf_and_spine_sprites_keep_their_payload() {
+        for (id, sprite_type) in [(1, SpriteType::Swf), (2, SpriteType::Spine)] {
+            let spr = parse(&sprt_chunk((4, 2), [0, 3, 1, 0], |w| {
+                gms2_header(w, 2, id, 30.0, false);
+                w.u32(0);
+                w.bytes(b"payload!");
+            }));
+
+            assert_eq!(spr.sprite_type, sprite_type);
+            assert_eq!(spr.payload.as_ref().map(SpritePayload::data), Some(&b"payload!"[..]));
+            assert_eq!(texture_addresses(&spr), (0, Vec::new()));
+            assert!(spr.collision_masks.is_empty());
+        }
+    }
+
+    #[test]
+    fn unknown_sprite_types() {
+        let spr = parse(&sprt_chunk((4, 2), [0, 3, 1, 0], |w| {
+            gms2_header(w, 2, 7, 30.0, false);
+            w.u32(0);
+        }));
+
+        assert_eq!(spr.sprite_type, SpriteType::Other(7));
+        assert!(spr.payload.is_none());
+        assert_eq!(texture_addresses(&spr), (0, Vec::new()));
+    }
+}
@@ -0,0 +1,74 @@
+//! Building chunks and files by hand, for the parser tests and (through `#[path]`) the integration tests.
+#![allow(dead_code)] // each test crate only uses some of these
+
+#[derive(Default)]
+pub struct Writer {
+    pub buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn pos(&self) -> u32 {
+        self.buf.len() as u32
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn u16(&mut self, v: u16) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, v: i32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, v: f32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    /// Writes a 0 to fill in later with `point_here` or `patch`, returning where it is.
+    pub fn placeholder(&mut self) -> usize {
+        self.u32(0);
+        self.buf.len() - 4
+    }
+
+    /// Fills in the placeholder at `at`.
+    pub fn patch(&mut self, at: usize, v: u32) {
+        self.buf[at..at + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    /// Makes the placeholder at `at` point to the current position.
+    pub fn point_here(&mut self, at: usize) {
+        let pos = self.pos();
+        self.patch(at, pos);
+    }
+
+    /// Writes a pointer list with `count` placeholders, returning them.
+    pub fn pointer_list(&mut self, count: usize) -> Vec<usize> {
+        self.u32(count as u32);
+        (0..count).map(|_| self.placeholder()).collect()
+    }
+
+    /// Writes a null-terminated string, returning its address.
+    pub fn string(&mut self, s: &str) -> u32 {
+        let pos = self.pos();
+        self.bytes(s.as_bytes());
+        self.bytes(&[0]);
+        pos
+    }
+
+    /// Writes a chunk with the contents written by `f`.
+    pub fn chunk<F: FnOnce(&mut Self)>(&mut self, id: [u8; 4], f: F) {
+        self.bytes(&id);
+        let len = self.placeholder();
+        let start = self.pos();
+        f(self);
+        let end = self.pos();
+        self.patch(len, end - start);
+    }
+}
@@ -0,0 +1,149 @@
+use std::convert::TryInto;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::{Chunk, read_string_ptr};
+
+
+#[derive(Debug)]
+pub struct Tgin {
+    pub version: u32,
+    pub groups: Vec<TextureGroupInfo>,
+}
+
+#[derive(Debug)]
+pub struct TextureGroupInfo {
+    pub name: String,
+    pub directory: String, // GM 2022.9+, empty before
+    pub extension: String, // GM 2022.9+, empty before
+    pub load_type: i32, // GM 2022.9+, 0 = in data.win, 1 = separate files
+    pub texture_pages: Vec<u32>, // indices into TXTR
+    pub sprites: Vec<u32>,
+    pub spine_sprites: Vec<u32>,
+    pub fonts: Vec<u32>,
+    pub tilesets: Vec<u32>,
+}
+
+fn read_id_list_ptr(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Vec<u32>> {
+    let addr = buf.read_u32::<LittleEndian>()?;
+    let pos_before = buf.position();
+    buf.set_position(addr.into());
+
+    let count = buf.read_u32::<LittleEndian>()?;
+    let ids = (0..count).map(|_| buf.read_u32::<LittleEndian>()).collect::<Result<Vec<u32>, std::io::Error>>()?;
+
+    buf.set_position(pos_before);
+    Ok(ids)
+}
+
+impl Chunk for Tgin {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+        let version = buf.read_u32::<LittleEndian>()?;
+        let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
+        let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
+        let mut groups = Vec::new();
+        for addr in entries_addrs {
+            buf.set_position(addr.try_into()?);
+
+            let name = read_string_ptr(buf)?;
+
+            // 2022.9+ adds directory, extension & load type after the name
+            // the old format only has pointers here, so a load type of 0 or 1 in the 3rd slot means it's the new one
+            buf.set_position(buf.position() + 8);
+            let dynamic = buf.read_u32::<LittleEndian>()? <= 1;
+            buf.set_position(buf.position() - 12);
+
+            let (directory, extension, load_type) = if dynamic {
+                (read_string_ptr(buf)?, read_string_ptr(buf)?, buf.read_i32::<LittleEndian>()?)
+            } else {
+                (String::new(), String::new(), 0)
+            };
+
+            let texture_pages = read_id_list_ptr(buf)?;
+            let sprites = read_id_list_ptr(buf)?;
+            let spine_sprites = read_id_list_ptr(buf)?;
+            let fonts = read_id_list_ptr(buf)?;
+            let tilesets = read_id_list_ptr(buf)?;
+
+            groups.push(TextureGroupInfo {
+                name,
+                directory,
+                extension,
+                load_type,
+                texture_pages,
+                sprites,
+                spine_sprites,
+                fonts,
+                tilesets,
+            });
+        }
+
+        Ok(Tgin {
+            version,
+            groups,
+        })
+    }
+
+    fn get_id() -> [u8; 4] {
+        *b"TGIN"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::chunk::testing::Writer;
+
+    /// A TGIN chunk with one group, in the 2022.9+ format if `dynamic`.
+    fn tgin_chunk(dynamic: bool) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.u32(1);
+        let [entry] = w.pointer_list(1)[..] else { unreachable!() };
+
+        w.point_here(entry);
+        let name = w.placeholder();
+        let (directory, extension) = if dynamic {
+            let strings = (w.placeholder(), w.placeholder());
+            w.u32(1);
+            (Some(strings.0), Some(strings.1))
+        } else {
+            (None, None)
+        };
+        let lists = (0..5).map(|_| w.placeholder()).collect::<Vec<_>>();
+
+        for (list, ids) in lists.into_iter().zip([&[0, 3][..], &[1], &[], &[2], &[4, 5, 6]]) {
+            w.point_here(list);
+            w.u32(ids.len() as u32);
+            for id in ids {
+                w.u32(*id);
+            }
+        }
+        for (ptr, s) in [(Some(name), "tg_ui"), (directory, "tex/"), (extension, ".png")] {
+            if let Some(ptr) = ptr {
+                w.point_here(ptr);
+                w.string(s);
+            }
+        }
+
+        w.buf
+    }
+
+    #[test]
+    fn parses_both_group_formats() {
+        for dynamic in [false, true] {
+            let tgin = Tgin::parse(&mut Cursor::new(&tgin_chunk(dynamic))).unwrap();
+            let [group] = &tgin.groups[..] else { panic!("expected one group") };
+
+            assert_eq!(group.name, "tg_ui");
+            assert_eq!((group.texture_pages.as_slice(), group.sprites.as_slice(), group.spine_sprites.as_slice()), (&[0, 3][..], &[1][..], &[][..]));
+            assert_eq!((group.fonts.as_slice(), group.tilesets.as_slice()), (&[2][..], &[4, 5, 6][..]));
+            if dynamic {
+                assert_eq!((group.directory.as_str(), group.extension.as_str(), group.load_type), ("tex/", ".png", 1));
+            } else {
+                assert_eq!((group.directory.as_str(), group.extension.as_str(), group.load_type), ("", "", 0));
+            }
+        }
+    }
+}
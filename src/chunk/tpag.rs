@@ -1,4 +1,4 @@
-use std::convert::TryInto;
+use std::{convert::TryInto, io::{Cursor, Read}};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
@@ -10,7 +10,7 @@ pub struct Tpag {
     pub textures: Vec<TextureEntry>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TextureEntry {
     pub x: u16,
     pub y: u16,
@@ -25,8 +25,45 @@ pub struct TextureEntry {
     pub spritesheet_id: u16,
 }
 
+impl TextureEntry {
+    pub fn read<R: Read>(buf: &mut R) -> anyhow::Result<Self> {
+        let x = buf.read_u16::<LittleEndian>()?;
+        let y = buf.read_u16::<LittleEndian>()?;
+        let width = buf.read_u16::<LittleEndian>()?;
+        let height = buf.read_u16::<LittleEndian>()?;
+        let render_x = buf.read_u16::<LittleEndian>()?;
+        let render_y = buf.read_u16::<LittleEndian>()?;
+        let bouding_x = buf.read_u16::<LittleEndian>()?;
+        let bouding_y = buf.read_u16::<LittleEndian>()?;
+        let bouding_width = buf.read_u16::<LittleEndian>()?;
+        let bouding_height = buf.read_u16::<LittleEndian>()?;
+        let spritesheet_id = buf.read_u16::<LittleEndian>()?;
+
+        Ok(TextureEntry {
+            x,
+            y,
+            width,
+            height,
+            render_x,
+            render_y,
+            bouding_x,
+            bouding_y,
+            bouding_width,
+            bouding_height,
+            spritesheet_id,
+        })
+    }
+
+    /// Reads the entry at `addr` (eg. a sprite's texture address) without needing a shared cursor.
+    pub fn read_at(data: &[u8], addr: u64) -> anyhow::Result<Self> {
+        let mut buf = Cursor::new(data);
+        buf.set_position(addr);
+        TextureEntry::read(&mut buf)
+    }
+}
+
 impl Chunk for Tpag {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
         let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
         let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
         let mut textures = Vec::new();
@@ -34,31 +71,7 @@ impl Chunk for Tpag {
             buf.set_position(addr.try_into()?);
             // println!("{}", buf.position());
 
-            let x = buf.read_u16::<LittleEndian>()?;
-            let y = buf.read_u16::<LittleEndian>()?;
-            let width = buf.read_u16::<LittleEndian>()?;
-            let height = buf.read_u16::<LittleEndian>()?;
-            let render_x = buf.read_u16::<LittleEndian>()?;
-            let render_y = buf.read_u16::<LittleEndian>()?;
-            let bouding_x = buf.read_u16::<LittleEndian>()?;
-            let bouding_y = buf.read_u16::<LittleEndian>()?;
-            let bouding_width = buf.read_u16::<LittleEndian>()?;
-            let bouding_height = buf.read_u16::<LittleEndian>()?;
-            let spritesheet_id = buf.read_u16::<LittleEndian>()?;
-
-            textures.push(TextureEntry {
-                x,
-                y,
-                width,
-                height,
-                render_x,
-                render_y,
-                bouding_x,
-                bouding_y,
-                bouding_width,
-                bouding_height,
-                spritesheet_id,
-            });
+            textures.push(TextureEntry::read(buf)?);
         }
 
         Ok(Tpag {
@@ -69,4 +82,45 @@ impl Chunk for Tpag {
     fn get_id() -> [u8; 4] {
         *b"TPAG"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl TextureEntry {
+    /// A `width`x`height` texture drawn at (`render_x`, `render_y`) on its sprite, for tests.
+    pub(crate) fn placed(render_x: u16, render_y: u16, width: u16, height: u16) -> Self {
+        TextureEntry {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            render_x,
+            render_y,
+            bouding_x: width,
+            bouding_y: height,
+            bouding_width: width,
+            bouding_height: height,
+            spritesheet_id: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_at_reads_from_any_address() {
+        let mut data = vec![0xaa; 3];
+        for v in 1..=11_u16 {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let entry = TextureEntry::read_at(&data, 3).unwrap();
+        assert_eq!((entry.x, entry.y, entry.width, entry.height), (1, 2, 3, 4));
+        assert_eq!((entry.render_x, entry.render_y), (5, 6));
+        assert_eq!((entry.bouding_x, entry.bouding_y, entry.bouding_width, entry.bouding_height), (7, 8, 9, 10));
+        assert_eq!(entry.spritesheet_id, 11);
+
+        assert!(TextureEntry::read_at(&data, 4).is_err());
+    }
+}
@@ -1,9 +1,10 @@
-use std::convert::TryInto;
+use std::{convert::{TryFrom, TryInto}, io::{Cursor, Read}};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use image::DynamicImage;
 
-use super::Chunk;
+use super::{Chunk, Tgin};
+use crate::qoi;
 
 
 #[derive(Debug)]
@@ -15,9 +16,22 @@ pub struct Txtr {
 pub struct SpritesheetEntry {
     pub _unknown1: u32,
     pub _unknown2: u32,
+    pub block_size: Option<u32>, // GM 2022.3+, size of the (compressed) texture data
+    pub texture_width: Option<i32>, // GM 2022.9+
+    pub texture_height: Option<i32>, // GM 2022.9+
+    pub index_in_group: Option<i32>, // GM 2022.9+
+    pub location: TextureLocation,
     pub png: PNGState,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureLocation {
+    Embedded,
+    External {
+        path: Option<String>, // relative to the game directory, resolved from TGIN (if present)
+    },
+}
+
 #[derive(Debug)]
 pub enum PNGState {
     Unloaded {
@@ -29,20 +43,34 @@ pub enum PNGState {
 }
 
 impl Chunk for Txtr {
-    fn parse(buf: &mut std::io::Cursor<Vec<u8>>) -> anyhow::Result<Self> where Self: std::marker::Sized {
+    fn parse(buf: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<Self> where Self: std::marker::Sized {
         let entries_addr_ct = buf.read_i32::<LittleEndian>()?;
         let entries_addrs = (0..entries_addr_ct).map(|_| buf.read_i32::<LittleEndian>()).collect::<Result<Vec<i32>, std::io::Error>>()?;
+        let entry_size = entry_size(buf, &entries_addrs);
+        debug!("TXTR has {} entries of {} bytes", entries_addrs.len(), entry_size);
+
         let mut spritesheets = Vec::new();
         for addr in entries_addrs {
             buf.set_position(addr.try_into()?);
 
             let unknown1 = buf.read_u32::<LittleEndian>()?;
             let unknown2 = buf.read_u32::<LittleEndian>()?; // this differs from the unpacking page, but is necessary now
+            let block_size = if entry_size >= 16 { Some(buf.read_u32::<LittleEndian>()?) } else { None };
+            let (texture_width, texture_height, index_in_group) = if entry_size >= 28 {
+                (Some(buf.read_i32::<LittleEndian>()?), Some(buf.read_i32::<LittleEndian>()?), Some(buf.read_i32::<LittleEndian>()?))
+            } else {
+                (None, None, None)
+            };
             let png_addr = buf.read_u32::<LittleEndian>()?;
 
             spritesheets.push(SpritesheetEntry {
                 _unknown1: unknown1,
                 _unknown2: unknown2,
+                block_size,
+                texture_width,
+                texture_height,
+                index_in_group,
+                location: if png_addr == 0 { TextureLocation::External { path: None } } else { TextureLocation::Embedded },
                 png: PNGState::Unloaded {
                     png_addr,
                 },
@@ -57,4 +85,244 @@ impl Chunk for Txtr {
     fn get_id() -> [u8; 4] {
         *b"TXTR"
     }
-}
\ No newline at end of file
+}
+
+impl Txtr {
+    /// Fills in the paths of external texture pages using the texture groups they belong to.
+    pub fn resolve_external_paths(&mut self, tgin: &Tgin) {
+        for group in &tgin.groups {
+            for (i, id) in group.texture_pages.iter().enumerate() {
+                if let Some(sheet) = self.spritesheets.get_mut(*id as usize) {
+                    if let TextureLocation::External { path } = &mut sheet.location {
+                        let index = sheet.index_in_group.map_or(i, |index| index as usize);
+                        *path = Some(format!("{}{}_{}{}", group.directory, group.name, index, group.extension));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Newer GM versions add fields to each entry, which we detect from the entry size.
+/// The entries are stored back to back (though not always in order), so the smallest gap between them works if there are at least two.
+/// Otherwise, we check which of the possible data pointers actually points to a texture.
+fn entry_size(buf: &mut Cursor<&[u8]>, entries_addrs: &[i32]) -> u32 {
+    let mut sorted = entries_addrs.to_vec();
+    sorted.sort_unstable();
+    let gap = sorted.windows(2).filter_map(|pair| u32::try_from(i64::from(pair[1]) - i64::from(pair[0])).ok()).filter(|gap| *gap > 0).min();
+    if let Some(gap) = gap {
+        return gap;
+    }
+
+    if let Some(addr) = entries_addrs.first() {
+        let pos_before = buf.position();
+        for size in [12, 16, 28] {
+            buf.set_position((*addr as u64) + u64::from(size) - 4);
+            let ptr = buf.read_u32::<LittleEndian>().unwrap_or(0) as usize;
+            if TextureFormat::detect(buf.get_ref().get(ptr..).unwrap_or_default()) != TextureFormat::Unknown {
+                buf.set_position(pos_before);
+                return size;
+            }
+        }
+        buf.set_position(pos_before);
+    }
+
+    12
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Png,
+    Qoi,
+    Bz2Qoi,
+    Unknown,
+}
+
+impl TextureFormat {
+    #[must_use]
+    pub fn detect(data: &[u8]) -> Self {
+        match data.get(..4) {
+            Some(b"\x89PNG") => TextureFormat::Png,
+            Some(b"fioq") => TextureFormat::Qoi,
+            Some(b"2zoq") => TextureFormat::Bz2Qoi,
+            _ => TextureFormat::Unknown,
+        }
+    }
+}
+
+/// Decodes a texture page, `data` should start at the texture's address.
+pub(crate) fn decode_texture(data: &[u8]) -> anyhow::Result<DynamicImage> {
+    let format = TextureFormat::detect(data);
+    trace!("decoding {:?} texture page ({} bytes)", format, data.len());
+
+    match format {
+        TextureFormat::Qoi => qoi::decode(data),
+        TextureFormat::Bz2Qoi => {
+            // '2zoq' + u16 width + u16 height, then (since 2022.5) a u32 with the uncompressed size
+            let bz2_start = if data.get(8..11) == Some(b"BZh") { 8 } else { 12 };
+            let mut qoi_bytes = Vec::new();
+            bzip2::read::BzDecoder::new(data.get(bz2_start..).unwrap_or_default()).read_to_end(&mut qoi_bytes)?;
+            qoi::decode(&qoi_bytes)
+        },
+        TextureFormat::Png | TextureFormat::Unknown => {
+            Ok(image::io::Reader::new(Cursor::new(data))
+                .with_guessed_format()?
+                .decode()?)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::chunk::{TextureGroupInfo, testing::Writer};
+
+    /// A 2x1 "fioq" image with a red and a green pixel.
+    fn qoi() -> Vec<u8> {
+        let mut bytes = b"fioq\x02\0\x01\0\x0a\0\0\0".to_vec();
+        bytes.extend_from_slice(&[0xff, 255, 0, 0, 255, 0xff, 0, 255, 0, 255]);
+        bytes
+    }
+
+    fn bz2(data: &[u8]) -> Vec<u8> {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn assert_red_green(texture: &DynamicImage) {
+        assert_eq!(texture.to_rgba8().pixels().map(|px| px.0).collect::<Vec<_>>(), [[255, 0, 0, 255], [0, 255, 0, 255]]);
+    }
+
+    #[test]
+    fn detects_texture_formats() {
+        assert_eq!(TextureFormat::detect(b"\x89PNG\r\n"), TextureFormat::Png);
+        assert_eq!(TextureFormat::detect(b"fioq...."), TextureFormat::Qoi);
+        assert_eq!(TextureFormat::detect(b"2zoq...."), TextureFormat::Bz2Qoi);
+        assert_eq!(TextureFormat::detect(b"fio"), TextureFormat::Unknown);
+    }
+
+    #[test]
+    fn decodes_qoi_textures() {
+        assert_red_green(&decode_texture(&qoi()).unwrap());
+    }
+
+    #[test]
+    fn decodes_bz2_qoi_textures_with_and_without_the_size() {
+        let qoi = qoi();
+
+        let mut old = b"2zoq\x02\0\x01\0".to_vec();
+        old.extend(bz2(&qoi));
+        assert_red_green(&decode_texture(&old).unwrap());
+
+        let mut new = b"2zoq\x02\0\x01\0".to_vec();
+        new.extend_from_slice(&(qoi.len() as u32).to_le_bytes());
+        new.extend(bz2(&qoi));
+        assert_red_green(&decode_texture(&new).unwrap());
+    }
+
+    /// A TXTR chunk with entries that have these fields before their data pointer, which points to a QOI texture if they're embedded.
+    fn txtr_chunk(entries: &[(&[u32], bool)]) -> (Vec<u8>, u32) {
+        let mut w = Writer::default();
+        let mut data_ptrs = Vec::new();
+        for (ptr, (fields, embedded)) in w.pointer_list(entries.len()).into_iter().zip(entries) {
+            w.point_here(ptr);
+            for v in *fields {
+                w.u32(*v);
+            }
+            let data_ptr = w.placeholder();
+            if *embedded {
+                data_ptrs.push(data_ptr);
+            }
+        }
+
+        let texture = w.pos();
+        for ptr in data_ptrs {
+            w.point_here(ptr);
+        }
+        w.bytes(&qoi());
+        (w.buf, texture)
+    }
+
+    fn parse(chunk: &[u8]) -> Txtr {
+        Txtr::parse(&mut Cursor::new(chunk)).unwrap()
+    }
+
+    #[test]
+    fn entry_size_is_the_distance_between_entries() {
+        let (chunk, texture) = txtr_chunk(&[(&[1, 0, 100, 64, 32, 3], false), (&[1, 0, 10, 2, 1, 0], true)]);
+        let txtr = parse(&chunk);
+
+        let external = &txtr.spritesheets[0];
+        assert_eq!((external.block_size, external.texture_width, external.texture_height, external.index_in_group), (Some(100), Some(64), Some(32), Some(3)));
+        assert_eq!(external.location, TextureLocation::External { path: None });
+
+        let embedded = &txtr.spritesheets[1];
+        assert_eq!(embedded.location, TextureLocation::Embedded);
+        assert!(matches!(embedded.png, PNGState::Unloaded { png_addr } if png_addr == texture));
+    }
+
+    #[test]
+    fn entries_can_be_out_of_order() {
+        let (mut chunk, texture) = txtr_chunk(&[(&[1, 0, 100, 64, 32, 3], false), (&[1, 0, 10, 2, 1, 0], true), (&[1, 0, 20, 4, 4, 1], false)]);
+        // list the entries as 2, 0, 1
+        let addrs = chunk[4..16].to_vec();
+        chunk[4..8].copy_from_slice(&addrs[8..12]);
+        chunk[8..16].copy_from_slice(&addrs[..8]);
+        let txtr = parse(&chunk);
+
+        let fields = txtr.spritesheets.iter().map(|sheet| (sheet.block_size, sheet.index_in_group)).collect::<Vec<_>>();
+        assert_eq!(fields, [(Some(20), Some(1)), (Some(100), Some(3)), (Some(10), Some(0))]);
+        assert!(matches!(txtr.spritesheets[2].png, PNGState::Unloaded { png_addr } if png_addr == texture));
+    }
+
+    #[test]
+    fn single_entries_are_sized_by_their_data_pointer() {
+        for fields in [&[1, 0][..], &[1, 0, 10], &[1, 0, 10, 2, 1, 0]] {
+            let (chunk, texture) = txtr_chunk(&[(fields, true)]);
+            let sheet = &parse(&chunk).spritesheets[0];
+
+            assert_eq!(sheet.block_size.is_some(), fields.len() >= 3, "{} fields", fields.len());
+            assert_eq!(sheet.index_in_group.is_some(), fields.len() >= 6, "{} fields", fields.len());
+            assert!(matches!(sheet.png, PNGState::Unloaded { png_addr } if png_addr == texture), "{} fields", fields.len());
+        }
+    }
+
+    #[test]
+    fn external_paths_come_from_texture_groups() {
+        let sheet = |location, index_in_group| SpritesheetEntry {
+            _unknown1: 0,
+            _unknown2: 0,
+            block_size: None,
+            texture_width: None,
+            texture_height: None,
+            index_in_group,
+            location,
+            png: PNGState::Unloaded { png_addr: 0 },
+        };
+        let external = TextureLocation::External { path: None };
+        let mut txtr = Txtr { spritesheets: vec![sheet(TextureLocation::Embedded, None), sheet(external.clone(), None), sheet(external, Some(5))] };
+
+        let group = |name: &str, texture_pages| TextureGroupInfo {
+            name: name.to_string(),
+            directory: "tex/".to_string(),
+            extension: ".png".to_string(),
+            load_type: 1,
+            texture_pages,
+            sprites: Vec::new(),
+            spine_sprites: Vec::new(),
+            fonts: Vec::new(),
+            tilesets: Vec::new(),
+        };
+        txtr.resolve_external_paths(&Tgin { version: 1, groups: vec![group("a", vec![0, 1]), group("b", vec![2, 7])] });
+
+        let locations = txtr.spritesheets.iter().map(|sheet| sheet.location.clone()).collect::<Vec<_>>();
+        assert_eq!(locations, [
+            TextureLocation::Embedded,
+            TextureLocation::External { path: Some("tex/a_1.png".to_string()) },
+            TextureLocation::External { path: Some("tex/b_5.png".to_string()) },
+        ]);
+    }
+}
@@ -0,0 +1,340 @@
+//! Options for how images are written when exporting sprites, backgrounds and fonts.
+
+use std::{collections::{HashMap, HashSet}, fs::{self, File}, io::{BufWriter, Write}, path::{Path, PathBuf}};
+
+use image::{DynamicImage, GenericImageView, RgbaImage, imageops::{self, FilterType}};
+
+use crate::{Atlas, BmFontFormat, chunk::{BackgroundEntry, BackgroundState, FontEntry, SpriteEntry, SpriteState}};
+
+/// How transparent pixels' colors are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Colors are independent of alpha (what PNG expects, and what GM stores).
+    Straight,
+    /// Colors are multiplied by alpha, for engines that blend that way.
+    Premultiplied,
+}
+
+/// How images are written by the `export_*` functions. The default writes them as they're stored:
+/// trimmed, at native size, as RGBA PNGs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Keep sprite frames trimmed to their visible area, instead of placing them on the sprite's full canvas (see `SpriteEntry::canvas_frames`).
+    pub trim: bool,
+    /// Integer nearest-neighbour upscaling, for pixel art (1 is native size, 0 is treated as 1).
+    pub scale: u32,
+    pub alpha: AlphaMode,
+    /// Write palette-indexed PNGs when an image has at most 256 colors (counting alpha), which is much smaller for pixel art.
+    /// Images with more colors are still written as RGBA.
+    pub indexed: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            trim: true,
+            scale: 1,
+            alpha: AlphaMode::Straight,
+            indexed: false,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// Applies the scale and alpha mode to an image.
+    #[must_use]
+    pub fn apply(&self, image: &DynamicImage) -> RgbaImage {
+        let scale = self.scale.max(1);
+        let mut out = if scale == 1 {
+            image.to_rgba8()
+        } else {
+            imageops::resize(image, image.width() * scale, image.height() * scale, FilterType::Nearest)
+        };
+
+        if self.alpha == AlphaMode::Premultiplied {
+            for px in out.pixels_mut() {
+                let a = u16::from(px[3]);
+                for c in 0..3 {
+                    px[c] = ((u16::from(px[c]) * a + 127) / 255) as u8;
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Writes an image as a PNG, see `apply` and `indexed`.
+    pub fn write_png<W: Write>(&self, writer: W, image: &DynamicImage) -> anyhow::Result<()> {
+        let image = self.apply(image);
+        if image.width() == 0 || image.height() == 0 {
+            return Err(anyhow::anyhow!("Can't write an empty image!"));
+        }
+
+        let indexed = if self.indexed { Indexed::new(&image) } else { None };
+
+        let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+        if let Some(indexed) = &indexed {
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(indexed.depth);
+            // filtering rarely helps palette images
+            encoder.set_filter(png::FilterType::NoFilter);
+            encoder.set_palette(indexed.palette.iter().flat_map(|px| px[..3].to_vec()).collect());
+            // tRNS can leave out the opaque colors at the end of the palette
+            let trns = indexed.palette.iter().map(|px| px[3]).take_while(|a| *a != 255).collect::<Vec<_>>();
+            if !trns.is_empty() {
+                encoder.set_trns(trns);
+            }
+        } else {
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+        }
+
+        // the IEND chunk is written when this is dropped
+        let mut png = encoder.write_header()?;
+        png.write_image_data(indexed.as_ref().map_or(image.as_raw(), |indexed| &indexed.data))?;
+
+        Ok(())
+    }
+
+    /// Saves an image as a PNG, see `write_png`.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P, image: &DynamicImage) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_png(&mut writer, image)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// These options without scaling, for images that were already scaled.
+    fn unscaled(self) -> Self {
+        Self { scale: 1, ..self }
+    }
+}
+
+/// An image as palette indices, packed at the smallest bit depth that fits.
+struct Indexed {
+    palette: Vec<[u8; 4]>,
+    depth: png::BitDepth,
+    data: Vec<u8>,
+}
+
+impl Indexed {
+    /// Returns `None` if the image has more than 256 colors.
+    fn new(image: &RgbaImage) -> Option<Self> {
+        let mut colors = HashSet::new();
+        for px in image.pixels() {
+            if colors.insert(px.0) && colors.len() > 256 {
+                return None;
+            }
+        }
+
+        // translucent colors first so tRNS can be short, and sorted so the output is the same every time
+        let mut palette = colors.into_iter().collect::<Vec<_>>();
+        palette.sort_unstable_by_key(|px| (px[3] == 255, *px));
+        let index = palette.iter().enumerate().map(|(i, px)| (*px, i as u8)).collect::<HashMap<_, _>>();
+
+        let (depth, bits) = match palette.len() {
+            0..=2 => (png::BitDepth::One, 1),
+            3..=4 => (png::BitDepth::Two, 2),
+            5..=16 => (png::BitDepth::Four, 4),
+            _ => (png::BitDepth::Eight, 8),
+        };
+
+        // each row starts on a new byte, with the first pixel in the high bits
+        let row_len = (image.width() as usize * bits).div_ceil(8);
+        let mut data = vec![0; row_len * image.height() as usize];
+        for (x, y, px) in image.enumerate_pixels() {
+            let bit = x as usize * bits;
+            data[y as usize * row_len + bit / 8] |= index[&px.0] << (8 - bits - bit % 8);
+        }
+
+        Some(Self {
+            palette,
+            depth,
+            data,
+        })
+    }
+}
+
+impl SpriteEntry {
+    /// Saves the loaded frames as `{name}.png`, or `{name}_N.png` if there's more than one, returning their paths.
+    /// The sprite must be loaded first (see `DataWin::load_sprite`).
+    pub fn export_frames<P: AsRef<Path>>(&self, name: &str, dir: P, options: &ExportOptions) -> anyhow::Result<Vec<PathBuf>> {
+        let canvas;
+        let frames: &[DynamicImage] = if options.trim {
+            match &self.textures {
+                SpriteState::Loaded { textures } => textures,
+                SpriteState::Unloaded { .. } => return Err(anyhow::anyhow!("Sprite must be loaded first!")),
+            }
+        } else {
+            canvas = self.canvas_frame_images()?;
+            &canvas
+        };
+
+        let dir = dir.as_ref();
+        frames.iter().enumerate().map(|(i, frame)| {
+            let path = if frames.len() == 1 { dir.join(format!("{}.png", name)) } else { dir.join(format!("{}_{}.png", name, i)) };
+            options.save_png(&path, frame)?;
+            Ok(path)
+        }).collect()
+    }
+}
+
+impl BackgroundEntry {
+    /// Saves the background's texture as a PNG. `trim` doesn't apply, backgrounds are always stored whole.
+    /// The background must be loaded first (see `DataWin::load_background`).
+    pub fn export_texture<P: AsRef<Path>>(&self, path: P, options: &ExportOptions) -> anyhow::Result<()> {
+        match &self.texture {
+            BackgroundState::Loaded { texture } => options.save_png(path, texture),
+            BackgroundState::Unloaded { .. } => Err(anyhow::anyhow!("Background must be loaded first (or it has no texture)!")),
+        }
+    }
+}
+
+impl FontEntry {
+    /// Saves each glyph (that has any pixels) as `{code}.png` in `dir`, named by character code since plenty of characters can't be in file names.
+    /// The font must be loaded first (see `DataWin::load_font`).
+    pub fn export_glyphs<P: AsRef<Path>>(&self, dir: P, options: &ExportOptions) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        for gly in self.glyphs.values().chain(self.unmapped_glyphs.values()) {
+            let texture = gly.texture.as_ref().ok_or_else(|| anyhow::anyhow!("Font must be loaded before exporting it!"))?;
+            if texture.width() > 0 && texture.height() > 0 {
+                options.save_png(dir.join(format!("{}.png", gly.code)), texture)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like `export_bmfont`, writing the atlas page with `options`. When scaling, the .fnt is scaled to match (see `bmfont_descriptor_scaled`).
+    pub fn export_bmfont_with<P: AsRef<Path>>(&self, name: &str, dir: P, format: BmFontFormat, options: &ExportOptions) -> anyhow::Result<()> {
+        let texture = self.texture.as_ref().ok_or_else(|| anyhow::anyhow!("Font must be loaded before exporting it!"))?;
+        let dir = dir.as_ref();
+
+        let page_file = format!("{}_0.png", name);
+        options.save_png(dir.join(&page_file), texture)?;
+        fs::write(dir.join(format!("{}.fnt", name)), self.bmfont_descriptor_scaled(&page_file, format, options.scale))?;
+
+        Ok(())
+    }
+}
+
+impl Atlas {
+    /// Like `save`, writing the texture with `options`. When scaling, the positions in the JSON are scaled too (see `scaled`).
+    pub fn save_with<P: AsRef<Path>>(&self, dir: P, name: &str, options: &ExportOptions) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        let image_file = format!("{}.png", name);
+
+        let scale = options.scale.max(1);
+        let atlas = self.scaled(scale);
+        let mut json = atlas.to_json(&image_file);
+        json["meta"]["scale"] = scale.to_string().into();
+
+        options.unscaled().save_png(dir.join(&image_file), &DynamicImage::ImageRgba8(atlas.texture))?;
+        fs::write(dir.join(format!("{}.json", name)), serde_json::to_string_pretty(&json)?)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use crate::{AtlasFrame, chunk::Glyph};
+
+    use super::*;
+
+    /// An image with `colors` different colors, the first of them transparent.
+    fn image_with_colors(colors: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(colors, 2, |x, _| {
+            Rgba([x as u8, (x >> 8) as u8, 7, if x == 0 { 0 } else { 255 }])
+        }))
+    }
+
+    fn write(options: ExportOptions, image: &DynamicImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        options.write_png(&mut png, image).unwrap();
+        png
+    }
+
+    #[test]
+    fn indexed_pngs_use_the_smallest_depth_and_round_trip() {
+        let options = ExportOptions { indexed: true, ..ExportOptions::default() };
+        for (colors, depth) in [(2, 1), (3, 2), (5, 4), (16, 4), (17, 8), (256, 8)] {
+            let image = image_with_colors(colors);
+            let png = write(options, &image);
+
+            // IHDR's bit depth and color type
+            assert_eq!((png[24], png[25]), (depth, 3), "{} colors", colors);
+            assert_eq!(image::load_from_memory(&png).unwrap().to_rgba8(), image.to_rgba8(), "{} colors", colors);
+        }
+    }
+
+    #[test]
+    fn indexed_pngs_only_store_alpha_for_translucent_colors() {
+        let png = write(ExportOptions { indexed: true, ..ExportOptions::default() }, &image_with_colors(5));
+        let trns = png.windows(4).position(|w| w == b"tRNS").unwrap();
+        assert_eq!(&png[trns - 4..trns + 5], [0, 0, 0, 1, b't', b'R', b'N', b'S', 0]);
+    }
+
+    #[test]
+    fn too_many_colors_are_written_as_rgba() {
+        let image = image_with_colors(257);
+        let png = write(ExportOptions { indexed: true, ..ExportOptions::default() }, &image);
+        assert_eq!((png[24], png[25]), (8, 6));
+        assert_eq!(image::load_from_memory(&png).unwrap().to_rgba8(), image.to_rgba8());
+    }
+
+    #[test]
+    fn apply_scales_and_premultiplies() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 1, Rgba([200, 100, 0, 128])));
+        let out = ExportOptions { scale: 3, alpha: AlphaMode::Premultiplied, ..ExportOptions::default() }.apply(&image);
+        assert_eq!(out.dimensions(), (6, 3));
+        assert!(out.pixels().all(|px| *px == Rgba([100, 50, 0, 128])));
+
+        // 0 is treated as 1
+        assert_eq!(ExportOptions { scale: 0, ..ExportOptions::default() }.apply(&image).dimensions(), (2, 1));
+    }
+
+    #[test]
+    fn scaled_atlases_scale_their_frames() {
+        let atlas = Atlas {
+            texture: RgbaImage::new(8, 4),
+            frames: vec![AtlasFrame {
+                sprite: "spr".to_string(),
+                index: 0,
+                x: 4,
+                y: 1,
+                width: 3,
+                height: 2,
+                trimmed: true,
+                offset_x: 1,
+                offset_y: 2,
+                source_width: 5,
+                source_height: 6,
+                origin_x: -1,
+                origin_y: 2,
+                duration_ms: 100,
+            }],
+        };
+
+        let scaled = atlas.scaled(2);
+        let frame = &scaled.frames[0];
+        assert_eq!(scaled.texture.dimensions(), (16, 8));
+        assert_eq!((frame.x, frame.y, frame.width, frame.height), (8, 2, 6, 4));
+        assert_eq!((frame.offset_x, frame.offset_y, frame.source_width, frame.source_height), (2, 4, 10, 12));
+        assert_eq!((frame.origin_x, frame.origin_y, frame.duration_ms), (-2, 4, 100));
+    }
+
+    #[test]
+    fn scaled_bmfonts_scale_their_metrics() {
+        let mut font = FontEntry::with_glyphs(vec![Glyph::new('A', 0, 4, 6, 5), Glyph::new('B', 4, 3, 6, 4)]);
+        font.glyphs.get_mut(&'B').unwrap().kerning.push((u16::from(b'A'), -1));
+
+        let out = font.bmfont_descriptor_scaled("page.png", BmFontFormat::Text, 2);
+        assert!(out.contains("size=24 "), "{}", out);
+        assert!(out.contains("common lineHeight=12 base=12 "), "{}", out);
+        assert!(out.contains("char id=66 x=8 y=0 width=6 height=12 xoffset=0 yoffset=0 xadvance=8 "), "{}", out);
+        assert!(out.contains("kerning first=65 second=66 amount=-2"), "{}", out);
+    }
+}
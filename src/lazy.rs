@@ -0,0 +1,137 @@
+//! `&self` versions of the loaders, which parse chunks and decode images on first access.
+//! The results are cached in the `DataWin` (under the texture cache's budget, see `DataWin::set_texture_cache_budget`), so these are cheap
+//! to call repeatedly (and from multiple threads, which wait for each other instead of decoding the same image twice).
+
+use std::{borrow::Cow, fs::{self, File}, path::PathBuf, sync::Arc};
+
+use image::DynamicImage;
+
+use crate::{DataWin, cache::{CacheKey, TextureSource}, audio_bufs, chunk::{AudioInfo, BackgroundState, SpriteState}, find_audio};
+
+impl DataWin {
+    fn texture_source(&self) -> anyhow::Result<TextureSource<'_>> {
+        Ok(TextureSource {
+            txtr: self.parse_txtr()?,
+            game_dir: self.game_dir.as_deref(),
+            cache: &self.texture_cache,
+        })
+    }
+
+    /// Gets the frames of a sprite, parsing SPRT/TXTR and decoding the spritesheets it uses if needed.
+    /// If the sprite was loaded with `load_sprite(s)`, these are copies of its textures.
+    pub fn sprite_frames(&self, name: &str) -> anyhow::Result<Vec<Arc<DynamicImage>>> {
+        let spr = self.parse_sprt()?.sprites.get(name).ok_or_else(|| anyhow::anyhow!("Sprite \"{}\" does not exist!", name))?;
+        spr.ensure_bitmap(name)?;
+
+        match &spr.textures {
+            // the sprite already holds its textures, so these copies aren't cached to avoid keeping them in memory twice
+            SpriteState::Loaded { textures } => Ok(textures.iter().cloned().map(Arc::new).collect()),
+            SpriteState::Unloaded { texture_count: _, texture_addresses } => self.texture_cache.get_or_try_insert_with(&CacheKey::Sprite(name.to_string()), || {
+                let textures = DataWin::sprite_textures_raw(&self.texture_source()?, &self.buf, texture_addresses, name)?;
+                Ok(textures.into_iter().map(|(_, texture)| Arc::new(texture)).collect())
+            }),
+        }
+    }
+
+    /// Gets a single frame of a sprite, see `sprite_frames`.
+    pub fn sprite_frame(&self, name: &str, frame: usize) -> anyhow::Result<Arc<DynamicImage>> {
+        let spr = self.parse_sprt()?.sprites.get(name).ok_or_else(|| anyhow::anyhow!("Sprite \"{}\" does not exist!", name))?;
+        let texture = match &spr.textures {
+            // only copy the frame we need
+            SpriteState::Loaded { textures } => textures.get(frame).cloned().map(Arc::new),
+            SpriteState::Unloaded { .. } => self.sprite_frames(name)?.get(frame).cloned(),
+        };
+        texture.ok_or_else(|| anyhow::anyhow!("Sprite \"{}\" does not have frame {}!", name, frame))
+    }
+
+    /// Gets the texture of a background/tileset, parsing BGND/TXTR and decoding the spritesheet it uses if needed.
+    /// Returns `None` if the background has no texture. If it was loaded with `load_background(s)`, this is a copy of its texture.
+    pub fn background_texture(&self, name: &str) -> anyhow::Result<Option<Arc<DynamicImage>>> {
+        let bg = self.parse_bgnd()?.backgrounds.get(name).ok_or_else(|| anyhow::anyhow!("Background \"{}\" does not exist!", name))?;
+
+        match &bg.texture {
+            // like loaded sprites, loaded backgrounds aren't cached again
+            BackgroundState::Loaded { texture } => Ok(Some(Arc::new(texture.clone()))),
+            BackgroundState::Unloaded { texture_address } => {
+                let images = self.texture_cache.get_or_try_insert_with(&CacheKey::Background(name.to_string()), || {
+                    let texture = DataWin::background_texture_raw(&self.texture_source()?, &self.buf, bg, *texture_address, name, &self.bgnd_rewrap_columns)?;
+                    Ok(texture.map(Arc::new).into_iter().collect())
+                })?;
+                Ok(images.into_iter().next())
+            },
+        }
+    }
+
+    /// Gets the texture containing all of a font's glyphs, parsing FONT/TXTR and decoding the spritesheet it uses if needed.
+    /// Each `Glyph`'s `relative_x`/`relative_y`/`width`/`height` is its area in this texture.
+    pub fn font_texture(&self, name: &str) -> anyhow::Result<Arc<DynamicImage>> {
+        let font = self.parse_font()?.fonts.get(name).ok_or_else(|| anyhow::anyhow!("Font \"{}\" does not exist!", name))?;
+
+        let images = self.texture_cache.get_or_try_insert_with(&CacheKey::Font(name.to_string()), || {
+            Ok(vec![Arc::new(DataWin::font_texture_raw(&self.texture_source()?, &self.buf, font)?)])
+        })?;
+        Ok(Arc::clone(&images[0]))
+    }
+
+    /// Gets the embedded audio data for a sound, parsing SOND/AUDO if needed.
+    /// Returns `None` if the sound is external (see `SoundEntry::file`).
+    pub fn sound_bytes(&self, name: &str) -> anyhow::Result<Option<&[u8]>> {
+        let sound = self.parse_sond()?.sounds.get(name).ok_or_else(|| anyhow::anyhow!("Sound \"{}\" does not exist!", name))?;
+
+        if sound.audio_id == -1 {
+            return Ok(None);
+        }
+
+        self.try_parse_agrp()?;
+        let audos = self.parse_audo()?;
+        let (buf, entry) = find_audio(audos, &audio_bufs(&self.buf, &self.audiogroups), self.agrp.get(), sound, name)?;
+
+        Ok(Some(&buf[entry.offset..entry.offset + entry.len]))
+    }
+
+    /// Gets a sound's audio file, whether it's embedded (see `sound_bytes`) or external.
+    /// External sounds are read from the game directory (see `DataWin::set_game_dir`) every time this is called.
+    pub fn sound_data(&self, name: &str) -> anyhow::Result<Cow<'_, [u8]>> {
+        if let Some(bytes) = self.sound_bytes(name)? {
+            return Ok(Cow::Borrowed(bytes));
+        }
+
+        let (path, file) = self.external_sound_path(name)?;
+        let bytes = fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read external sound \"{}\" ({}): {}", name, file, e))?;
+
+        Ok(Cow::Owned(bytes))
+    }
+
+    /// Reads the format/metadata of a sound's audio without decoding it, see `sound_data`.
+    /// Only the headers of external sounds are read, see `AudioInfo::read_from`.
+    pub fn sound_info(&self, name: &str) -> anyhow::Result<AudioInfo> {
+        if let Some(bytes) = self.sound_bytes(name)? {
+            return Ok(AudioInfo::read(bytes));
+        }
+
+        let (path, file) = self.external_sound_path(name)?;
+        File::open(path).and_then(AudioInfo::read_from).map_err(|e| anyhow::anyhow!("Failed to read external sound \"{}\" ({}): {}", name, file, e))
+    }
+
+    /// Where an external sound is, as a full path and as it's named in the game.
+    fn external_sound_path(&self, name: &str) -> anyhow::Result<(PathBuf, String)> {
+        let sound = self.parse_sond()?.sounds.get(name).ok_or_else(|| anyhow::anyhow!("Sound \"{}\" does not exist!", name))?;
+        let file = sound.external_file();
+        let game_dir = self.game_dir.as_deref().ok_or_else(|| anyhow::anyhow!("Sound \"{}\" is external ({}), but no game directory is set!", name, file))?;
+
+        Ok((game_dir.join(&file), file))
+    }
+
+    /// Decodes a sound's audio to PCM samples, see `sound_data`.
+    #[cfg(feature = "audio")]
+    pub fn sound_pcm(&self, name: &str) -> anyhow::Result<crate::Pcm> {
+        crate::Pcm::decode(&self.sound_data(name)?)
+    }
+
+    /// Decodes a sound's audio with its `volume` and `pitch` applied, see `sound_pcm` and `SoundEntry::render_as_played`.
+    #[cfg(feature = "audio")]
+    pub fn render_sound(&self, name: &str) -> anyhow::Result<crate::Pcm> {
+        let sound = self.parse_sond()?.sounds.get(name).ok_or_else(|| anyhow::anyhow!("Sound \"{}\" does not exist!", name))?;
+        Ok(self.sound_pcm(name)?.as_played(sound.volume, sound.pitch))
+    }
+}
\ No newline at end of file
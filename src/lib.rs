@@ -3,374 +3,682 @@
 #![warn(clippy::pedantic)]
 
 #![allow(clippy::cast_sign_loss)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::pub_underscore_fields)]
 #![allow(clippy::missing_errors_doc)]
+#![allow(clippy::uninlined_format_args)]
 
-use chunk::{AudioType, Audo, BackgroundEntry, Bgnd, Font, Gen8, Optn, PNGState, Sond, SoundEntry, SpriteEntry, SpriteState, Sprt, TextureEntry, Tpag, Txtr};
+use chunk::{Agrp, AudioData, AudioEntry, AudioType, Audo, BackgroundEntry, Bgnd, Font, FontEntry, Gen8, Optn, PNGState, Sond, SoundEntry, SpriteEntry, SpriteState, SpritesheetEntry, Sprt, TextureEntry, TextureLocation, Tgin, Tpag, Txtr};
 use image::{GenericImageView, DynamicImage, imageops};
 
-use std::{collections::HashMap, convert::TryInto, fs, io::{self, Cursor, Read}, path::Path};
+use std::{collections::HashMap, convert::TryInto, fs, io::{self, Cursor, Read}, path::{Path, PathBuf}, sync::Arc};
 use byteorder::{LittleEndian, ReadBytesExt};
+use once_cell::sync::OnceCell;
 
-use crate::chunk::{BackgroundState, Chunk};
+use crate::{cache::{CacheKey, TextureCache, TextureSource}, chunk::{BackgroundState, Chunk}, progress::{Hooks, Tracker}};
+
+#[macro_use]
+mod logging;
 
 pub mod chunk;
+mod animation;
+mod atlas;
+#[cfg(feature = "audio")]
+mod audio;
+mod bmfont;
+mod cache;
+mod export;
+mod lazy;
+mod progress;
+mod qoi;
+mod text;
+
+#[cfg(feature = "audio")]
+pub use audio::Pcm;
+#[cfg(feature = "animation")]
+pub use animation::AnimationFormat;
+pub use atlas::{Atlas, AtlasFrame, AtlasLayout};
+pub use bmfont::BmFontFormat;
+pub use cache::DEFAULT_TEXTURE_CACHE_BUDGET;
+pub use export::{AlphaMode, ExportOptions};
+pub use progress::{CancelToken, Cancelled, Phase, Progress};
 
 pub fn prepare_file<P: AsRef<Path>>(path: P, audiogroup_paths: Vec<P>) -> Result<DataWinReady, anyhow::Error> {
-    prepare_bytes(fs::read(path.as_ref())?, audiogroup_paths.into_iter().map(|path| fs::read(path.as_ref())).collect::<io::Result<Vec<Vec<u8>>>>()?)
+    let mut ready = prepare_bytes(fs::read(path.as_ref())?, audiogroup_paths.into_iter().map(|path| fs::read(path.as_ref())).collect::<io::Result<Vec<Vec<u8>>>>()?)?;
+    // external files (eg. texture pages) are relative to the folder data.win is in
+    ready.game_dir = path.as_ref().parent().map(Path::to_path_buf);
+    Ok(ready)
 }
 
 pub fn prepare_bytes(bytes: Vec<u8>, audiogroup_bytes: Vec<Vec<u8>>) -> Result<DataWinReady, anyhow::Error> {
     let n_bytes = bytes.len();
 
-    // TODO: log::info!
-    // println!("Given {} bytes...", n_bytes);
+    info!("Given {} bytes, {} audiogroup(s)", n_bytes, audiogroup_bytes.len());
 
     let buf = Cursor::new(bytes);
     let audiogroup_bufs = audiogroup_bytes.into_iter().map(Cursor::new).collect();
 
     Ok(DataWinReady {
-        n_bytes,
         buf,
         audiogroup_bufs,
+        game_dir: None,
     })
 }
 
+/// Describes a sound's audio group for errors, eg. `audio group 2 ("audiogroup_music", audiogroup2.dat)`.
+fn describe_audio_group(agrp: Option<&Agrp>, group_id: i32) -> String {
+    match agrp.and_then(|agrp| agrp.group(group_id)) {
+        Some(group) => format!("audio group {} (\"{}\", {})", group_id, group.name, group.file_name(group_id).unwrap_or_else(|| "data.win".to_string())),
+        None => format!("audio group {} (audiogroup{}.dat)", group_id, group_id),
+    }
+}
+
+/// The buffers AUDO chunks are in, indexed by audio group (data.win first).
+fn audio_bufs<'a>(buf: &'a Arc<Vec<u8>>, audiogroups: &'a [AudioGroupFile]) -> Vec<&'a Arc<Vec<u8>>> {
+    std::iter::once(buf).chain(audiogroups.iter().map(|file| &file.buf)).collect()
+}
+
+/// Finds a sound's embedded audio, and the buffer it's in.
+fn find_audio<'a>(audos: &[Audo], bufs: &[&'a Arc<Vec<u8>>], agrp: Option<&Agrp>, sound: &SoundEntry, name: &str) -> anyhow::Result<(&'a Arc<Vec<u8>>, AudioEntry)> {
+    let group = sound.group_id as usize;
+    let (audo, buf) = audos.get(group).zip(bufs.get(group))
+        .ok_or_else(|| anyhow::anyhow!("Sound \"{}\" is in {}, but that file was not loaded!", name, describe_audio_group(agrp, sound.group_id)))?;
+    let entry = audo.sounds.get(sound.audio_id as usize)
+        .ok_or_else(|| anyhow::anyhow!("Sound \"{}\" points to missing audio {} in {}!", name, sound.audio_id, describe_audio_group(agrp, sound.group_id)))?;
+
+    Ok((buf, *entry))
+}
+
+/// Runs `f` on each entry (as a step of `tracker`), in parallel if the `parallel` feature is enabled.
+fn for_each_entry<V, F>(entries: Vec<(&String, &mut V)>, tracker: &Tracker, f: F) -> anyhow::Result<()>
+where
+    V: Send,
+    F: Fn(&String, &mut V) -> anyhow::Result<()> + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        entries.into_par_iter().try_for_each(|(k, v)| tracker.step(|| f(k, v)))
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        entries.into_iter().try_for_each(|(k, v)| tracker.step(|| f(k, v)))
+    }
+}
+
+/// Runs `f` on every entry in `map` whose name matches `pred` (see `for_each_entry`), returning the matched names sorted.
+/// Only the matching entries count towards the progress.
+fn for_each_matching<V, P, F>(map: &mut HashMap<String, V>, hooks: &Hooks, phase: Phase, pred: P, f: F) -> anyhow::Result<Vec<String>>
+where
+    V: Send,
+    P: Fn(&str) -> bool,
+    F: Fn(&String, &mut V) -> anyhow::Result<()> + Send + Sync,
+{
+    let entries = map.iter_mut().filter(|(name, _)| pred(name)).collect::<Vec<_>>();
+    let mut names = entries.iter().map(|(name, _)| (*name).clone()).collect::<Vec<_>>();
+    names.sort();
+
+    let tracker = hooks.start(phase, entries.len());
+    for_each_entry(entries, &tracker, f)?;
+
+    Ok(names)
+}
+
+/// Parses a glob pattern like `spr_kris_*` for the `load_*_matching` functions.
+fn glob_pattern(pattern: &str) -> anyhow::Result<glob::Pattern> {
+    glob::Pattern::new(pattern).map_err(|e| anyhow::anyhow!("Invalid pattern \"{}\": {}", pattern, e))
+}
+
 pub struct DataWinReady {
-    n_bytes: usize,
     buf: Cursor<Vec<u8>>,
     audiogroup_bufs: Vec<Cursor<Vec<u8>>>,
+    game_dir: Option<PathBuf>,
 }
 
-impl DataWinReady {
-    pub fn fetch_chunks(mut self) -> Result<DataWin, anyhow::Error> {
-
-        let mut form_chunk_name_buf = [0_u8; 4];
-        self.buf.read_exact(&mut form_chunk_name_buf)?;
-        let _form_chunk_len = self.buf.read_i32::<LittleEndian>()?;
-
-        let mut chunk_addrs = HashMap::new();
-        
-        if &form_chunk_name_buf == b"FORM" {
-            while self.buf.position() < self.n_bytes.try_into()? {
-                let mut chunk_name_buf = [0_u8; 4];
-                self.buf.read_exact(&mut chunk_name_buf)?;
-                let chunk_len = self.buf.read_i32::<LittleEndian>()?;
-    
-                // TODO: log::debug!
-                // println!("chunk {}, len: {}", String::from_utf8_lossy(&chunk_name_buf), chunk_len);
+/// Walks the chunks in a FORM file, returning where each chunk's data starts.
+fn read_chunk_addrs(buf: &mut Cursor<&[u8]>) -> anyhow::Result<HashMap<[u8; 4], u64>> {
+    let mut form_chunk_name_buf = [0_u8; 4];
+    buf.read_exact(&mut form_chunk_name_buf)?;
+    let form_chunk_len = buf.read_u32::<LittleEndian>()?;
 
-                let this_chunk_pos = self.buf.position();
+    if &form_chunk_name_buf != b"FORM" {
+        return Err(anyhow::anyhow!("Could not find \"FORM\" chunk!"));
+    }
 
-                self.buf.set_position(this_chunk_pos + chunk_len as u64);
+    let n_bytes = buf.get_ref().len() as u64;
+    let form_end = (buf.position() + u64::from(form_chunk_len)).min(n_bytes);
+    let mut chunk_addrs = HashMap::new();
 
-                chunk_addrs.insert(chunk_name_buf, this_chunk_pos);
-            }
+    while buf.position() < form_end {
+        let mut chunk_name_buf = [0_u8; 4];
+        buf.read_exact(&mut chunk_name_buf)?;
+        let chunk_len = buf.read_u32::<LittleEndian>()?;
+
+        debug!("chunk {} at {:#x}, len: {}", String::from_utf8_lossy(&chunk_name_buf), buf.position(), chunk_len);
 
-            Ok(DataWin {
-                buf: self.buf,
-                audiogroup_bufs: self.audiogroup_bufs,
-                chunk_addrs,
-                gen8: None,
-                optn: None,
-                sond: None,
-                sprt: None,
-                tpag: None,
-                txtr: None,
-                audo: None,
-                font: None,
-                bgnd: None,
-                bgnd_rewrap_columns: HashMap::new(),
-            })
-        }else {
-            Err(anyhow::anyhow!("Could not find \"FORM\" chunk!"))
+        let this_chunk_pos = buf.position();
+        if this_chunk_pos + u64::from(chunk_len) > n_bytes {
+            return Err(anyhow::anyhow!("Chunk \"{}\" at {:#x} is {} bytes long, which goes past the end of the file!", String::from_utf8_lossy(&chunk_name_buf), this_chunk_pos, chunk_len));
         }
+
+        buf.set_position(this_chunk_pos + u64::from(chunk_len));
+
+        chunk_addrs.insert(chunk_name_buf, this_chunk_pos);
     }
+
+    Ok(chunk_addrs)
 }
 
+fn chunk_infos(buf: &[u8], chunk_addrs: &HashMap<[u8; 4], u64>) -> Vec<ChunkInfo> {
+    let mut chunks = chunk_addrs.iter().map(|(id, addr)| {
+        // the length is stored right before the chunk's data
+        let len_bytes = buf.get((*addr as usize - 4)..(*addr as usize)).and_then(|b| b.try_into().ok()).unwrap_or([0; 4]);
+        ChunkInfo {
+            id: *id,
+            addr: *addr,
+            len: u32::from_le_bytes(len_bytes),
+        }
+    }).collect::<Vec<_>>();
+    chunks.sort_by_key(|chunk| chunk.addr);
+    chunks
+}
+
+/// An audiogroupN.dat file, which is a FORM file like data.win (usually with only an AUDO chunk).
+#[derive(Debug)]
+struct AudioGroupFile {
+    buf: Arc<Vec<u8>>,
+    chunk_addrs: HashMap<[u8; 4], u64>,
+}
+
+impl AudioGroupFile {
+    fn new(buf: Vec<u8>, group_id: usize) -> anyhow::Result<Self> {
+        let chunk_addrs = read_chunk_addrs(&mut Cursor::new(buf.as_slice()))
+            .map_err(|e| anyhow::anyhow!("Failed to read audio group {}: {}", group_id, e))?;
+
+        if !chunk_addrs.contains_key(&Audo::get_id()) {
+            return Err(anyhow::anyhow!("Audio group {} has no AUDO chunk!", group_id));
+        }
+
+        Ok(AudioGroupFile {
+            buf: Arc::new(buf),
+            chunk_addrs,
+        })
+    }
+}
+
+impl DataWinReady {
+    pub fn fetch_chunks(self) -> Result<DataWin, anyhow::Error> {
+        let buf = self.buf.into_inner();
+        let chunk_addrs = read_chunk_addrs(&mut Cursor::new(buf.as_slice()))?;
+
+        // audio group N is in the N-1th file, since group 0 is in data.win
+        let audiogroups = self.audiogroup_bufs.into_iter().enumerate()
+            .map(|(i, buf)| AudioGroupFile::new(buf.into_inner(), i + 1))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(DataWin {
+            buf: Arc::new(buf),
+            audiogroups,
+            game_dir: self.game_dir,
+            chunk_addrs,
+            gen8: OnceCell::new(),
+            optn: OnceCell::new(),
+            sond: OnceCell::new(),
+            sprt: OnceCell::new(),
+            tpag: OnceCell::new(),
+            txtr: OnceCell::new(),
+            audo: OnceCell::new(),
+            font: OnceCell::new(),
+            bgnd: OnceCell::new(),
+            tgin: OnceCell::new(),
+            agrp: OnceCell::new(),
+            bgnd_rewrap_columns: HashMap::new(),
+            texture_cache: TextureCache::new(DEFAULT_TEXTURE_CACHE_BUDGET),
+            hooks: Hooks::default(),
+        })
+    }
+}
+
+/// A data.win with its chunk positions loaded.
+///
+/// Chunks are parsed once (on the first `parse_*` call, or when something needs them) and kept in `OnceCell`s,
+/// so a `DataWin` can be shared between threads and used through `&self` (see `sprite_frames` etc.).
+/// The `load_*` functions still need `&mut self` since they store the loaded assets in the parsed chunks.
 #[derive(Debug)]
 pub struct DataWin {
-    buf: Cursor<Vec<u8>>,
-    audiogroup_bufs: Vec<Cursor<Vec<u8>>>,
+    buf: Arc<Vec<u8>>, // shared with loaded sounds (see `AudioData`)
+    audiogroups: Vec<AudioGroupFile>,
+    game_dir: Option<PathBuf>,
     chunk_addrs: HashMap<[u8; 4], u64>,
-    pub gen8: Option<Gen8>,
-    pub optn: Option<Optn>,
-    pub sond: Option<Sond>,
-    pub sprt: Option<Sprt>,
-    pub tpag: Option<Tpag>,
-    pub txtr: Option<Txtr>,
-    pub audo: Option<Vec<Audo>>,
-    pub font: Option<Font>,
-    pub bgnd: Option<Bgnd>,
+    pub gen8: OnceCell<Gen8>,
+    pub optn: OnceCell<Optn>,
+    pub sond: OnceCell<Sond>,
+    pub sprt: OnceCell<Sprt>,
+    pub tpag: OnceCell<Tpag>,
+    pub txtr: OnceCell<Txtr>,
+    pub audo: OnceCell<Vec<Audo>>,
+    pub font: OnceCell<Font>,
+    pub bgnd: OnceCell<Bgnd>,
+    pub tgin: OnceCell<Tgin>,
+    pub agrp: OnceCell<Agrp>,
     bgnd_rewrap_columns: HashMap<String, u32>,
+    texture_cache: TextureCache,
+    hooks: Hooks,
+}
+
+/// Where a chunk is in the file, see `DataWin::chunks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub id: [u8; 4],
+    pub addr: u64, // start of the chunk's data (after the name & length)
+    pub len: u32,
+}
+
+#[allow(dead_code)]
+fn assert_data_win_send_sync() {
+    fn assert<T: Send + Sync>() {}
+    assert::<DataWin>();
 }
 
 impl DataWin {
-    fn parse_chunk<T: Chunk>(&mut self) -> anyhow::Result<T> {
+    /// Opens the data file in a game's directory (`data.win`, or `game.unx`/`game.ios`/`game.droid` on other platforms),
+    /// along with the audio group files for every group in its AGRP chunk.
+    pub fn open_dir<P: AsRef<Path>>(game_dir: P) -> anyhow::Result<DataWin> {
+        let game_dir = game_dir.as_ref();
+        let path = ["data.win", "game.unx", "game.ios", "game.droid"].iter()
+            .map(|name| game_dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| anyhow::anyhow!("Could not find a data.win in {}!", game_dir.display()))?;
+
+        DataWin::open_file(path)
+    }
+
+    /// Opens a data file, along with the audio group files next to it for every group in its AGRP chunk.
+    /// Without an AGRP chunk, every `audiogroupN.dat` next to it is loaded (until one is missing).
+    pub fn open_file<P: AsRef<Path>>(path: P) -> anyhow::Result<DataWin> {
+        let path = path.as_ref();
+        let mut data = prepare_file(path, Vec::new())
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?
+            .fetch_chunks()?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut audiogroups = Vec::new();
+
+        if data.chunk_addrs.contains_key(&Agrp::get_id()) {
+            // group 0 is the default group, which is in data.win itself
+            for (id, group) in data.parse_agrp()?.groups.iter().enumerate().skip(1) {
+                let file = group.file_name(id.try_into()?).unwrap_or_default();
+                let bytes = fs::read(dir.join(&file))
+                    .map_err(|e| anyhow::anyhow!("Failed to read {} for audio group {} (\"{}\"): {}", file, id, group.name, e))?;
+                audiogroups.push(AudioGroupFile::new(bytes, id)?);
+            }
+        } else {
+            for id in 1.. {
+                match fs::read(dir.join(format!("audiogroup{}.dat", id))) {
+                    Ok(bytes) => audiogroups.push(AudioGroupFile::new(bytes, id)?),
+                    Err(_) => break,
+                }
+            }
+        }
+
+        debug!("found {} audio group file(s)", audiogroups.len());
+        data.audiogroups = audiogroups;
+        Ok(data)
+    }
+
+    /// Sets the directory external files (eg. texture pages) are loaded from.
+    /// `prepare_file` defaults this to the folder containing data.win.
+    pub fn set_game_dir<P: Into<PathBuf>>(&mut self, game_dir: P) {
+        self.game_dir = Some(game_dir.into());
+    }
+
+    #[must_use]
+    pub fn game_dir(&self) -> Option<&Path> {
+        self.game_dir.as_deref()
+    }
+
+    /// Reports progress of chunk parsing and the bulk loaders to `progress`.
+    pub fn set_progress<P: Progress + 'static>(&mut self, progress: P) {
+        self.hooks.progress = Some(std::sync::Arc::new(progress));
+    }
+
+    /// Makes chunk parsing and the bulk loaders stop with a `Cancelled` error once `token` is cancelled.
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.hooks.cancel = Some(token);
+    }
+
+    /// Lists the chunks in the file, in the order they appear.
+    #[must_use]
+    pub fn chunks(&self) -> Vec<ChunkInfo> {
+        chunk_infos(&self.buf, &self.chunk_addrs)
+    }
+
+    /// Lists the chunks in an audio group's file (`group_id` >= 1, see `SoundEntry::group_id`), in the order they appear.
+    /// Returns `None` if that group's file wasn't loaded.
+    #[must_use]
+    pub fn audiogroup_chunks(&self, group_id: usize) -> Option<Vec<ChunkInfo>> {
+        let file = self.audiogroups.get(group_id.checked_sub(1)?)?;
+        Some(chunk_infos(&file.buf, &file.chunk_addrs))
+    }
+
+    /// Gets the raw data of a chunk in an audio group's file, eg. for chunks other than AUDO that this library doesn't parse.
+    #[must_use]
+    pub fn audiogroup_chunk_data(&self, group_id: usize, id: [u8; 4]) -> Option<&[u8]> {
+        let file = self.audiogroups.get(group_id.checked_sub(1)?)?;
+        let info = chunk_infos(&file.buf, &file.chunk_addrs).into_iter().find(|chunk| chunk.id == id)?;
+        file.buf.get(info.addr as usize..(info.addr + u64::from(info.len)) as usize)
+    }
+
+    /// The number of audio group files that were loaded (not counting the default group in data.win).
+    #[must_use]
+    pub fn audiogroup_count(&self) -> usize {
+        self.audiogroups.len()
+    }
+
+    fn parse_chunk<T: Chunk>(&self) -> anyhow::Result<T> {
         if let Some(addr) = self.chunk_addrs.get(&T::get_id()) {
-            self.buf.set_position(*addr);
-            T::parse(&mut self.buf)
+            let mut buf = Cursor::new(self.buf.as_slice());
+            buf.set_position(*addr);
+
+            let id = String::from_utf8_lossy(&T::get_id()).into_owned();
+            debug!("parsing {} at {:#x}", id, addr);
+            let start = std::time::Instant::now();
+            let chunk = self.hooks.start(Phase::Parse(T::get_id()), 1).step(|| T::parse(&mut buf))?;
+            debug!("parsed {} in {:?}", id, start.elapsed());
+
+            Ok(chunk)
         } else {
             Err(anyhow::anyhow!("Chunk \"{}\" is not present!", String::from_utf8_lossy(&T::get_id())))
         }
     }
 
-    pub fn parse_gen8(&mut self) -> anyhow::Result<()> {
-        if self.gen8.is_none() {
-            self.gen8 = Some(self.parse_chunk::<Gen8>()?);
-        }
-        Ok(())
+    pub fn parse_gen8(&self) -> anyhow::Result<&Gen8> {
+        self.gen8.get_or_try_init(|| self.parse_chunk::<Gen8>())
     }
 
-    pub fn parse_optn(&mut self) -> anyhow::Result<()> {
-        if self.optn.is_none() {
-            self.optn = Some(self.parse_chunk::<Optn>()?);
-        }
-        Ok(())
+    pub fn parse_optn(&self) -> anyhow::Result<&Optn> {
+        self.optn.get_or_try_init(|| self.parse_chunk::<Optn>())
     }
 
-    pub fn parse_sond(&mut self) -> anyhow::Result<()> {
-        if self.sond.is_none() {
-            self.sond = Some(self.parse_chunk::<Sond>()?);
-        }
-        Ok(())
+    pub fn parse_sond(&self) -> anyhow::Result<&Sond> {
+        self.sond.get_or_try_init(|| self.parse_chunk::<Sond>())
     }
 
-    pub fn parse_sprt(&mut self) -> anyhow::Result<()> {
-        if self.sprt.is_none() {
-            self.sprt = Some(self.parse_chunk::<Sprt>()?);
-        }
-        Ok(())
+    pub fn parse_sprt(&self) -> anyhow::Result<&Sprt> {
+        self.sprt.get_or_try_init(|| self.parse_chunk::<Sprt>())
     }
 
-    pub fn parse_tpag(&mut self) -> anyhow::Result<()> {
-        if self.tpag.is_none() {
-            self.tpag = Some(self.parse_chunk::<Tpag>()?);
-        }
-        Ok(())
+    pub fn parse_tpag(&self) -> anyhow::Result<&Tpag> {
+        self.tpag.get_or_try_init(|| self.parse_chunk::<Tpag>())
     }
 
-    pub fn parse_txtr(&mut self) -> anyhow::Result<()> {
-        if self.txtr.is_none() {
-            self.txtr = Some(self.parse_chunk::<Txtr>()?);
-        }
-        Ok(())
+    pub fn parse_txtr(&self) -> anyhow::Result<&Txtr> {
+        self.txtr.get_or_try_init(|| {
+            let mut txtr = self.parse_chunk::<Txtr>()?;
+
+            // the paths for external texture pages come from their texture group
+            let has_external = txtr.spritesheets.iter().any(|spr| spr.location != TextureLocation::Embedded);
+            if has_external && self.chunk_addrs.contains_key(&Tgin::get_id()) {
+                debug!("resolving external texture page paths from TGIN");
+                txtr.resolve_external_paths(self.parse_tgin()?);
+            }
+
+            Ok(txtr)
+        })
     }
 
-    pub fn parse_bgnd(&mut self) -> anyhow::Result<()> {
-        if self.bgnd.is_none() {
-            self.bgnd = Some(self.parse_chunk::<Bgnd>()?);
-        }
-        Ok(())
+    pub fn parse_tgin(&self) -> anyhow::Result<&Tgin> {
+        self.tgin.get_or_try_init(|| self.parse_chunk::<Tgin>())
+    }
+
+    pub fn parse_agrp(&self) -> anyhow::Result<&Agrp> {
+        self.agrp.get_or_try_init(|| self.parse_chunk::<Agrp>())
     }
 
-    pub fn parse_audo(&mut self) -> anyhow::Result<()> {
-        if self.audo.is_none() {
+    pub fn parse_bgnd(&self) -> anyhow::Result<&Bgnd> {
+        self.bgnd.get_or_try_init(|| self.parse_chunk::<Bgnd>())
+    }
+
+    pub fn parse_audo(&self) -> anyhow::Result<&Vec<Audo>> {
+        self.audo.get_or_try_init(|| {
             let mut audo_v = vec![self.parse_chunk::<Audo>()?];
 
-            for buf in &mut self.audiogroup_bufs {
-                buf.set_position(16); // manually skip 'FORM' + u32 length & 'AUDO' + u32 length since AUDO is the only chunk in these files
-                audo_v.push(Audo::parse(buf)?);
+            let tracker = self.hooks.start(Phase::Parse(Audo::get_id()), self.audiogroups.len());
+            for (i, file) in self.audiogroups.iter().enumerate() {
+                let addr = file.chunk_addrs[&Audo::get_id()]; // checked when the file was loaded
+                debug!("parsing AUDO in audiogroup {} at {:#x}", i + 1, addr);
+                let mut buf = Cursor::new(file.buf.as_slice());
+                buf.set_position(addr);
+                audo_v.push(tracker.step(|| Audo::parse(&mut buf))?);
             }
 
-            self.audo = Some(audo_v);
+            Ok(audo_v)
+        })
+    }
+
+    pub fn parse_font(&self) -> anyhow::Result<&Font> {
+        self.font.get_or_try_init(|| self.parse_chunk::<Font>())
+    }
+
+    pub(crate) fn decode_spritesheet(spr: &SpritesheetEntry, data: &[u8], game_dir: Option<&Path>) -> anyhow::Result<DynamicImage> {
+        match (&spr.location, &spr.png) {
+            (_, PNGState::Loaded { texture }) => Ok(texture.clone()),
+            (TextureLocation::Embedded, PNGState::Unloaded { png_addr }) => {
+                let start = *png_addr as usize;
+                let end = spr.block_size.map_or(data.len(), |size| (start + size as usize).min(data.len()));
+                chunk::decode_texture(data.get(start..end).unwrap_or_default())
+            },
+            (TextureLocation::External { path: Some(path) }, PNGState::Unloaded { .. }) => {
+                let game_dir = game_dir.ok_or_else(|| anyhow::anyhow!("Spritesheet is external ({}), but no game directory is set!", path))?;
+                let bytes = fs::read(game_dir.join(path)).map_err(|e| anyhow::anyhow!("Failed to read external spritesheet {}: {}", path, e))?;
+                chunk::decode_texture(&bytes)
+            },
+            (TextureLocation::External { path: None }, PNGState::Unloaded { .. }) => {
+                Err(anyhow::anyhow!("Spritesheet is external, but its path is unknown (missing TGIN chunk?)"))
+            },
         }
-        Ok(())
     }
 
-    pub fn parse_font(&mut self) -> anyhow::Result<()> {
-        if self.font.is_none() {
-            self.font = Some(self.parse_chunk::<Font>()?);
+    fn load_spritesheet_raw(spr: &mut SpritesheetEntry, data: &[u8], game_dir: Option<&Path>) -> anyhow::Result<()> {
+        if let PNGState::Unloaded { .. } = spr.png {
+            let texture = DataWin::decode_spritesheet(spr, data, game_dir)?;
+
+            spr.png = PNGState::Loaded {
+                texture
+            };
         }
+
         Ok(())
     }
 
+    /// Sets the memory budget (in bytes of decoded image data) for spritesheets that are decoded on demand,
+    /// and the sprites/backgrounds/fonts cached by `sprite_frames` etc. When it is exceeded, the least recently used ones are dropped.
+    /// Spritesheets loaded with `load_spritesheets` (and assets loaded with the `load_*` functions) do not count towards this.
+    pub fn set_texture_cache_budget(&self, budget: usize) {
+        self.texture_cache.set_budget(budget);
+    }
+
+    /// Drops all spritesheets that were decoded on demand, and the sprites/backgrounds/fonts cached by `sprite_frames` etc.
+    pub fn clear_texture_cache(&self) {
+        self.texture_cache.clear();
+    }
+
+    /// Loads every spritesheet, parsing TXTR first if needed.
     pub fn load_spritesheets(&mut self) -> anyhow::Result<()> {
+        self.parse_txtr()?;
+        if let Some(txtr) = self.txtr.get_mut() {
+            let data = &self.buf;
+            let game_dir = self.game_dir.as_deref();
+            let tracker = self.hooks.start(Phase::Spritesheets, txtr.spritesheets.len());
 
-        if let Some(txtr) = &mut self.txtr {
-            
             #[cfg(feature = "parallel")]
             {
                 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
-                let buf = self.buf.clone();
-                txtr.spritesheets.par_iter_mut().map::<_, anyhow::Result<()>>(|spr| {
-                    let mut buf = buf.clone();
-                    if let PNGState::Unloaded { png_addr } = spr.png {
-                        buf.set_position(png_addr.into());
-    
-                        let texture = image::io::Reader::new(&mut buf)
-                            .with_guessed_format()?
-                            .decode()?;
-    
-                        spr.png = PNGState::Loaded {
-                            texture
-                        };
-                    }
-
-                    Ok(())
+                txtr.spritesheets.par_iter_mut().map(|spr| {
+                    tracker.step(|| DataWin::load_spritesheet_raw(spr, data, game_dir))
                 }).collect::<anyhow::Result<Vec<()>>>()?;
             }
 
             #[cfg(not(feature = "parallel"))]
             {
                 for spr in &mut txtr.spritesheets {
-                    if let PNGState::Unloaded { png_addr } = spr.png {
-                        self.buf.set_position(png_addr.into());
-    
-                        let texture = image::io::Reader::new(&mut self.buf)
-                            .with_guessed_format()?
-                            .decode()?;
-    
-                        spr.png = PNGState::Loaded {
-                            texture
-                        };
-                    }
+                    tracker.step(|| DataWin::load_spritesheet_raw(spr, data, game_dir))?;
                 }
             }
-        } else {
-            return Err(anyhow::anyhow!("TXTR chunk must be parsed before calling load_spritesheets!"));
         }
 
         Ok(())
     }
 
-    fn load_sprite_raw(txtr: &mut Txtr, buf: &mut Cursor<Vec<u8>>, spr: &mut SpriteEntry, name: &str) -> anyhow::Result<()> {
-        if let SpriteState::Unloaded { texture_count: _, texture_addresses } = &spr.textures {
+    fn sprite_textures_raw(source: &TextureSource, data: &[u8], texture_addresses: &[i32], name: &str) -> anyhow::Result<Vec<(TextureEntry, DynamicImage)>> {
+        let mut textures = Vec::new();
 
-            let mut textures = Vec::new();
-
-            for addr in texture_addresses {
-                buf.set_position(*addr as u64);
-
-                if *addr == 0 {
-                    // TODO: log::debug!
-                    // println!("sprite {} has a texture_addr == 0", name);
-                }else{
-                    let x = buf.read_u16::<LittleEndian>()?;
-                    let y = buf.read_u16::<LittleEndian>()?;
-                    let width = buf.read_u16::<LittleEndian>()?;
-                    let height = buf.read_u16::<LittleEndian>()?;
-                    let render_x = buf.read_u16::<LittleEndian>()?;
-                    let render_y = buf.read_u16::<LittleEndian>()?;
-                    let bouding_x = buf.read_u16::<LittleEndian>()?;
-                    let bouding_y = buf.read_u16::<LittleEndian>()?;
-                    let bouding_width = buf.read_u16::<LittleEndian>()?;
-                    let bouding_height = buf.read_u16::<LittleEndian>()?;
-                    let spritesheet_id = buf.read_u16::<LittleEndian>()?;
-
-                    let tex = TextureEntry {
-                        x,
-                        y,
-                        width,
-                        height,
-                        render_x,
-                        render_y,
-                        bouding_x,
-                        bouding_y,
-                        bouding_width,
-                        bouding_height,
-                        spritesheet_id,
-                    };
-
-                    // self.txtr.unwrap() is safe because of the check at the start of the fn
-                    let sheet = &mut txtr.spritesheets[tex.spritesheet_id as usize];
-                    let texture = match &mut sheet.png {
-                        PNGState::Loaded { texture } => {
-                            Ok(texture.crop(u32::from(tex.x), u32::from(tex.y), u32::from(tex.width), u32::from(tex.height)))
-                        }
-                        PNGState::Unloaded{ .. } => Err(anyhow::anyhow!("Spritesheet not loaded!")),
-                    }?;
-
-                    textures.push(texture);
-                }
+        for addr in texture_addresses {
+            if *addr == 0 {
+                debug!("sprite {} has a texture_addr == 0", name);
+            }else{
+                let tex = TextureEntry::read_at(data, *addr as u64)?;
+
+                let sheet = source.sheet(data, tex.spritesheet_id as usize)?;
+                let texture = sheet.crop_imm(u32::from(tex.x), u32::from(tex.y), u32::from(tex.width), u32::from(tex.height));
+
+                textures.push((tex, texture));
             }
+        }
 
-            // assert_eq!(textures.len(), texture_addresses.len()); // not true if addr == 0
+        // assert_eq!(textures.len(), texture_addresses.len()); // not true if addr == 0
+
+        Ok(textures)
+    }
+
+    fn load_sprite_raw(source: &TextureSource, data: &[u8], spr: &mut SpriteEntry, name: &str) -> anyhow::Result<()> {
+        if let SpriteState::Unloaded { texture_count: _, texture_addresses } = &spr.textures {
+            let (frame_entries, textures) = DataWin::sprite_textures_raw(source, data, texture_addresses, name)?.into_iter().unzip();
 
             spr.textures = SpriteState::Loaded {
                 textures
             };
+            spr.frame_entries = frame_entries;
+            // `sprite_frames` reads loaded sprites directly, so a copy it cached earlier isn't needed anymore
+            source.cache.remove(&CacheKey::Sprite(name.to_string()));
         }
 
         Ok(())
     }
 
     pub fn load_sprite<S: Into<String>>(&mut self, name: S) -> anyhow::Result<()> {
-        if let Some(sprt) = &mut self.sprt {
-            // we don't actually use the values already stored in TPAG since the sprite only knows the address, not the index...
-            //   not really sure if it would be worth adding a hashmap or something to save the TPAG entries' addresses so we can look them up here?
-            if let Some(txtr) = &mut self.txtr {
-                let name = &name.into();
-                if let Some(spr) = sprt.sprites.get_mut(name) {
-                    DataWin::load_sprite_raw(txtr, &mut self.buf, spr, name)?;
-                }
-            } else {
-                return Err(anyhow::anyhow!("TXTR chunk must be parsed before calling load_sprites!"));
-            }
-        } else {
-            return Err(anyhow::anyhow!("SPRT chunk must be parsed before calling load_sprites!"));
+        // we don't actually use the values already stored in TPAG since the sprite only knows the address, not the index...
+        //   not really sure if it would be worth adding a hashmap or something to save the TPAG entries' addresses so we can look them up here?
+        self.parse_txtr()?;
+        let (Some(sprt), Some(txtr)) = (self.sprt.get_mut(), self.txtr.get()) else {
+            return Err(anyhow::anyhow!("SPRT chunk must be parsed before calling load_sprite!"));
+        };
+
+        let source = TextureSource { txtr, game_dir: self.game_dir.as_deref(), cache: &self.texture_cache };
+        let name = &name.into();
+        if let Some(spr) = sprt.sprites.get_mut(name) {
+            spr.ensure_bitmap(name)?;
+            DataWin::load_sprite_raw(&source, &self.buf, spr, name)?;
         }
 
         Ok(())
     }
 
     pub fn load_sprites(&mut self) -> anyhow::Result<()> {
+        self.load_sprites_where(|_| true)?;
+        Ok(())
+    }
 
-        if let Some(sprt) = &mut self.sprt {
-            // we don't actually use the values already stored in TPAG since the sprite only knows the address, not the index...
-            //   not really sure if it would be worth adding a hashmap or something to save the TPAG entries' addresses so we can look them up here?
-
-            // if let Some(tpag) = &mut self.tpag {
-                if let Some(txtr) = &mut self.txtr {
-                    for (name, spr) in &mut sprt.sprites {
-                        DataWin::load_sprite_raw(txtr, &mut self.buf, spr, name)?;
-                    }
-                } else {
-                    return Err(anyhow::anyhow!("TXTR chunk must be parsed before calling load_sprites!"));
-                }
-            // }
-        } else {
-            return Err(anyhow::anyhow!("SPRT chunk must be parsed before calling load_sprites!"));
-        }
+    /// Loads the sprites whose name matches a glob pattern (eg. `spr_kris_*`), returning their names.
+    pub fn load_sprites_matching(&mut self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        let pattern = glob_pattern(pattern)?;
+        self.load_sprites_where(|name| pattern.matches(name))
+    }
 
-        Ok(())
+    /// Loads the sprites whose name `pred` returns true for (eg. a regex match), returning their names.
+    pub fn load_sprites_where<P: Fn(&str) -> bool + Sync>(&mut self, pred: P) -> anyhow::Result<Vec<String>> {
+        self.parse_txtr()?;
+        let (Some(sprt), Some(txtr)) = (self.sprt.get_mut(), self.txtr.get()) else {
+            return Err(anyhow::anyhow!("SPRT chunk must be parsed before calling load_sprites!"));
+        };
+
+        let source = TextureSource { txtr, game_dir: self.game_dir.as_deref(), cache: &self.texture_cache };
+        let data = &self.buf;
+        for_each_matching(&mut sprt.sprites, &self.hooks, Phase::Sprites, pred, |name, spr| {
+            // SWF/Spine sprites have no frames to load, their data is already in `payload`
+            if let Err(e) = spr.ensure_bitmap(name) {
+                debug!("skipping sprite: {}", e);
+                return Ok(());
+            }
+            DataWin::load_sprite_raw(&source, data, spr, name)
+        })
     }
 
-    #[allow(clippy::unnecessary_wraps)]
-    fn load_sound_raw(sound: &mut SoundEntry, audos: &mut Vec<Audo>) -> anyhow::Result<()> {
+    fn load_sound_raw(sound: &mut SoundEntry, name: &str, audos: &[Audo], bufs: &[&Arc<Vec<u8>>], agrp: Option<&Agrp>) -> anyhow::Result<()> {
         if sound.audio_data.is_none() {
             if sound.audio_id == -1 {
-                sound.audio_data = Some(AudioType::External);
+                sound.audio_data = Some(AudioType::External {
+                    path: sound.external_file(),
+                });
             } else {
-                // TODO: is cloning the data here necessary? not sure if multiple sounds can have the same audio_id
-                sound.audio_data = Some(AudioType::Internal(audos[sound.group_id as usize].sounds[sound.audio_id as usize].clone()));
+                // this only shares the buffer, the audio isn't copied
+                let (buf, entry) = find_audio(audos, bufs, agrp, sound, name)?;
+                let data = AudioData::new(Arc::clone(buf), entry);
+                sound.audio_data = Some(AudioType::Internal {
+                    format: chunk::AudioFormat::detect(&data),
+                    data,
+                });
             }
         }
 
         Ok(())
     }
 
+    /// Parses AGRP if the file has it, so errors can name audio groups.
+    fn try_parse_agrp(&self) -> anyhow::Result<()> {
+        if self.chunk_addrs.contains_key(&Agrp::get_id()) {
+            self.parse_agrp()?;
+        }
+        Ok(())
+    }
+
     pub fn load_sounds(&mut self) -> anyhow::Result<()> {
-        if let Some(sond) = &mut self.sond {
-            if let Some(audos) = &mut self.audo {
-                for sound in sond.sounds.values_mut() {
-                    DataWin::load_sound_raw(sound, audos)?;
-                }
+        self.load_sounds_where(|_| true)?;
+        Ok(())
+    }
+
+    /// Loads the sounds whose name matches a glob pattern (eg. `snd_*`), returning their names.
+    pub fn load_sounds_matching(&mut self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        let pattern = glob_pattern(pattern)?;
+        self.load_sounds_where(|name| pattern.matches(name))
+    }
+
+    /// Loads the sounds whose name `pred` returns true for (eg. a regex match), returning their names.
+    pub fn load_sounds_where<P: Fn(&str) -> bool + Sync>(&mut self, pred: P) -> anyhow::Result<Vec<String>> {
+        self.try_parse_agrp()?;
+        if let Some(sond) = self.sond.get_mut() {
+            if let Some(audos) = self.audo.get() {
+                let agrp = self.agrp.get();
+                let bufs = audio_bufs(&self.buf, &self.audiogroups);
+                for_each_matching(&mut sond.sounds, &self.hooks, Phase::Sounds, pred, |name, sound| DataWin::load_sound_raw(sound, name, audos, &bufs, agrp))
             } else {
-                return Err(anyhow::anyhow!("AUDO chunk must be parsed before calling load_sounds!"));
+                Err(anyhow::anyhow!("AUDO chunk must be parsed before calling load_sounds!"))
             }
         } else {
-            return Err(anyhow::anyhow!("SOND chunk must be parsed before calling load_sounds!"));
+            Err(anyhow::anyhow!("SOND chunk must be parsed before calling load_sounds!"))
         }
-
-        Ok(())
     }
 
     pub fn load_sound<S: Into<String>>(&mut self, name: S) -> anyhow::Result<()> {
-        if let Some(sond) = &mut self.sond {
-            if let Some(audos) = &mut self.audo {
-                if let Some(sound) = sond.sounds.get_mut(&name.into()) {
-                    DataWin::load_sound_raw(sound, audos)?;
+        self.try_parse_agrp()?;
+        if let Some(sond) = self.sond.get_mut() {
+            if let Some(audos) = self.audo.get() {
+                let name = &name.into();
+                if let Some(sound) = sond.sounds.get_mut(name) {
+                    DataWin::load_sound_raw(sound, name, audos, &audio_bufs(&self.buf, &self.audiogroups), self.agrp.get())?;
                 }
             } else {
                 return Err(anyhow::anyhow!("AUDO chunk must be parsed before calling load_sound!"));
@@ -382,148 +690,105 @@ impl DataWin {
         Ok(())
     }
     
-    pub fn load_fonts(&mut self) -> anyhow::Result<()> {
+    fn font_texture_raw(source: &TextureSource, data: &[u8], font: &FontEntry) -> anyhow::Result<DynamicImage> {
+        let tex = TextureEntry::read_at(data, u64::from(font.tpag_addr))?;
 
-        if let Some(font) = &mut self.font {
-            // we don't actually use the values already stored in TPAG since the sprite only knows the address, not the index...
-            //   not really sure if it would be worth adding a hashmap or something to save the TPAG entries' addresses so we can look them up here?
-
-            for (code_name, font) in &mut font.fonts {
-                // if let Some(tpag) = &mut self.tpag {
-                    if let Some(txtr) = &mut self.txtr {
-                        let buf = &mut self.buf;
-
-                        buf.set_position(u64::from(font.tpag_addr));
-
-                        let x = buf.read_u16::<LittleEndian>()?;
-                        let y = buf.read_u16::<LittleEndian>()?;
-                        let width = buf.read_u16::<LittleEndian>()?;
-                        let height = buf.read_u16::<LittleEndian>()?;
-                        let render_x = buf.read_u16::<LittleEndian>()?;
-                        let render_y = buf.read_u16::<LittleEndian>()?;
-                        let bouding_x = buf.read_u16::<LittleEndian>()?;
-                        let bouding_y = buf.read_u16::<LittleEndian>()?;
-                        let bouding_width = buf.read_u16::<LittleEndian>()?;
-                        let bouding_height = buf.read_u16::<LittleEndian>()?;
-                        let spritesheet_id = buf.read_u16::<LittleEndian>()?;
-
-                        let tex = TextureEntry {
-                            x,
-                            y,
-                            width,
-                            height,
-                            render_x,
-                            render_y,
-                            bouding_x,
-                            bouding_y,
-                            bouding_width,
-                            bouding_height,
-                            spritesheet_id,
-                        };
-
-                        let sheet = &mut txtr.spritesheets[tex.spritesheet_id as usize];
-                        let mut texture = match &mut sheet.png {
-                            PNGState::Loaded { texture } => {
-                                Ok(texture.crop(u32::from(tex.x), u32::from(tex.y), u32::from(tex.width), u32::from(tex.height)))
-                            }
-                            PNGState::Unloaded{ .. } => Err(anyhow::anyhow!("Spritesheet not loaded!")),
-                        }?;
-
-                        for gly in font.glyphs.values_mut() {
-                            gly.texture = Some(texture.crop(gly.relative_x.into(), gly.relative_y.into(), gly.width.max(1).into(), gly.height.max(1).into()));
-                        }
-                    } else {
-                        return Err(anyhow::anyhow!("TXTR chunk must be parsed before calling load_sprites!"));
-                    }
-                // }
-            }
-        } else {
-            return Err(anyhow::anyhow!("SPRT chunk must be parsed before calling load_sprites!"));
+        let sheet = source.sheet(data, tex.spritesheet_id as usize)?;
+        Ok(sheet.crop_imm(u32::from(tex.x), u32::from(tex.y), u32::from(tex.width), u32::from(tex.height)))
+    }
+
+    fn load_font_raw(source: &TextureSource, data: &[u8], font: &mut FontEntry) -> anyhow::Result<()> {
+        let mut texture = DataWin::font_texture_raw(source, data, font)?;
+
+        for gly in font.glyphs.values_mut().chain(font.unmapped_glyphs.values_mut()) {
+            gly.texture = Some(texture.crop(gly.relative_x.into(), gly.relative_y.into(), gly.width.max(1).into(), gly.height.max(1).into()));
         }
+        font.texture = Some(texture);
 
         Ok(())
     }
-    
+
+    pub fn load_fonts(&mut self) -> anyhow::Result<()> {
+        self.load_fonts_where(|_| true)?;
+        Ok(())
+    }
+
+    /// Loads the fonts whose name matches a glob pattern (eg. `fnt_main*`), returning their names.
+    pub fn load_fonts_matching(&mut self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        let pattern = glob_pattern(pattern)?;
+        self.load_fonts_where(|name| pattern.matches(name))
+    }
+
+    /// Loads the fonts whose name `pred` returns true for (eg. a regex match), returning their names.
+    pub fn load_fonts_where<P: Fn(&str) -> bool + Sync>(&mut self, pred: P) -> anyhow::Result<Vec<String>> {
+        self.parse_txtr()?;
+        let (Some(font), Some(txtr)) = (self.font.get_mut(), self.txtr.get()) else {
+            return Err(anyhow::anyhow!("FONT chunk must be parsed before calling load_fonts!"));
+        };
+
+        let source = TextureSource { txtr, game_dir: self.game_dir.as_deref(), cache: &self.texture_cache };
+        let data = &self.buf;
+        for_each_matching(&mut font.fonts, &self.hooks, Phase::Fonts, pred, |_, font| DataWin::load_font_raw(&source, data, font))
+    }
+
+    /// Sets how many tile columns to lay these backgrounds' tiles out in, by name. Backgrounds already cached by `background_texture`
+    /// are decoded again the next time they're asked for, but ones loaded with `load_background(s)` keep their texture.
     pub fn add_background_rewrap_columns(&mut self, bgnd_rewrap_columns: HashMap<String, u32>) {
+        for name in bgnd_rewrap_columns.keys() {
+            self.texture_cache.remove(&CacheKey::Background(name.clone()));
+        }
         self.bgnd_rewrap_columns.extend(bgnd_rewrap_columns);
     }
 
-    fn load_background_raw(txtr: &mut Txtr, buf: &mut Cursor<Vec<u8>>, bg: &mut BackgroundEntry, name: &str, bgnd_rewrap_columns: &mut HashMap<String, u32>) -> anyhow::Result<()> {
-        if let BackgroundState::Unloaded { texture_address } = &bg.texture {
+    fn background_texture_raw(source: &TextureSource, data: &[u8], bg: &BackgroundEntry, texture_address: i32, name: &str, bgnd_rewrap_columns: &HashMap<String, u32>) -> anyhow::Result<Option<DynamicImage>> {
+        if texture_address == 0 {
+            debug!("background {} has a texture_addr == 0", name);
+            return Ok(None);
+        }
 
-            buf.set_position(*texture_address as u64);
+        let tex = TextureEntry::read_at(data, texture_address as u64)?;
 
-            if *texture_address == 0 {
-                // TODO: log::debug!
-                // println!("sprite {} has a texture_addr == 0", name);
-            }else{
-                let x = buf.read_u16::<LittleEndian>()?;
-                let y = buf.read_u16::<LittleEndian>()?;
-                let width = buf.read_u16::<LittleEndian>()?;
-                let height = buf.read_u16::<LittleEndian>()?;
-                let render_x = buf.read_u16::<LittleEndian>()?;
-                let render_y = buf.read_u16::<LittleEndian>()?;
-                let bouding_x = buf.read_u16::<LittleEndian>()?;
-                let bouding_y = buf.read_u16::<LittleEndian>()?;
-                let bouding_width = buf.read_u16::<LittleEndian>()?;
-                let bouding_height = buf.read_u16::<LittleEndian>()?;
-                let spritesheet_id = buf.read_u16::<LittleEndian>()?;
-
-                let tex = TextureEntry {
-                    x,
-                    y,
-                    width,
-                    height,
-                    render_x,
-                    render_y,
-                    bouding_x,
-                    bouding_y,
-                    bouding_width,
-                    bouding_height,
-                    spritesheet_id,
-                };
+        let sheet = source.sheet(data, tex.spritesheet_id as usize)?;
+        let mut texture = sheet.crop_imm(u32::from(tex.x), u32::from(tex.y), u32::from(tex.width), u32::from(tex.height));
+
+        if let Some(rewrap_columns) = bgnd_rewrap_columns.get(&name.to_string()).copied() {
+            if rewrap_columns != bg.columns {
+                debug!("rewrapping background {} from {} to {} columns", name, bg.columns, rewrap_columns);
+                let size = bg.tile_width + bg.margin_x * 2;
+
+                let old_t_w = texture.width() / size;
+                let old_t_h = texture.height() / size;
+                let old_area_t = old_t_w * old_t_h;
+
+                let new_t_w = rewrap_columns;
+                let new_t_h = (f64::from(old_area_t) / f64::from(new_t_w)).ceil() as u32;
 
-                // self.txtr.unwrap() is safe because of the check at the start of the fn
-                let sheet = &mut txtr.spritesheets[tex.spritesheet_id as usize];
-                let mut texture = match &mut sheet.png {
-                    PNGState::Loaded { texture } => {
-                        Ok(texture.crop(u32::from(tex.x), u32::from(tex.y), u32::from(tex.width), u32::from(tex.height)))
-                    }
-                    PNGState::Unloaded{ .. } => Err(anyhow::anyhow!("Spritesheet not loaded!")),
-                }?;
-
-                if let Some(rewrap_columns) = bgnd_rewrap_columns.get(&name.to_string()).copied() {
-                    if rewrap_columns != bg.columns {
-                        // println!("reflowing {} from {} to {} ({})", name, bg.columns, rewrap_columns, rewrap_columns as i32 - bg.columns as i32);
-                        // println!("{} {}", bg.columns, bg.rewrap_columns as i32 - bg.columns as i32);
-                        let size = bg.tile_width + bg.margin_x * 2;
-
-                        let old_t_w = texture.width() / size;
-                        let old_t_h = texture.height() / size;
-                        let old_area_t = old_t_w * old_t_h;
-
-                        let new_t_w = rewrap_columns;
-                        let new_t_h = (f64::from(old_area_t) / f64::from(new_t_w)).ceil() as u32;
-
-                        let mut new_tex = DynamicImage::new_rgba8(new_t_w * size, new_t_h * size);
-
-                        for y in 0..old_t_h {
-                            for x in 0..old_t_w {
-                                let index = x + y * old_t_w;
-                                let nx = index % new_t_w;
-                                let ny = index / new_t_w;
-                                let sub = texture.crop_imm(x * size, y * size, size, size);
-                                imageops::overlay(&mut new_tex, &sub, nx * size, ny * size);
-                            }   
-                        }
-
-                        texture = new_tex;
-                    }
+                let mut new_tex = DynamicImage::new_rgba8(new_t_w * size, new_t_h * size);
+
+                for y in 0..old_t_h {
+                    for x in 0..old_t_w {
+                        let index = x + y * old_t_w;
+                        let nx = index % new_t_w;
+                        let ny = index / new_t_w;
+                        let sub = texture.crop_imm(x * size, y * size, size, size);
+                        imageops::overlay(&mut new_tex, &sub, nx * size, ny * size);
+                    }   
                 }
 
+                texture = new_tex;
+            }
+        }
+
+        Ok(Some(texture))
+    }
+
+    fn load_background_raw(source: &TextureSource, data: &[u8], bg: &mut BackgroundEntry, name: &str, bgnd_rewrap_columns: &HashMap<String, u32>) -> anyhow::Result<()> {
+        if let BackgroundState::Unloaded { texture_address } = bg.texture {
+            if let Some(texture) = DataWin::background_texture_raw(source, data, bg, texture_address, name, bgnd_rewrap_columns)? {
                 bg.texture = BackgroundState::Loaded {
                     texture
                 };
+                source.cache.remove(&CacheKey::Background(name.to_string()));
             }
         }
 
@@ -531,43 +796,94 @@ impl DataWin {
     }
 
     pub fn load_background<S: Into<String>>(&mut self, name: S) -> anyhow::Result<()> {
-        if let Some(bgnd) = &mut self.bgnd {
-            // we don't actually use the values already stored in TPAG since the sprite only knows the address, not the index...
-            //   not really sure if it would be worth adding a hashmap or something to save the TPAG entries' addresses so we can look them up here?
-            if let Some(txtr) = &mut self.txtr {
-                let name = &name.into();
-                if let Some(bg) = bgnd.backgrounds.get_mut(name) {
-                    DataWin::load_background_raw(txtr, &mut self.buf, bg, name, &mut self.bgnd_rewrap_columns)?;
-                }
-            } else {
-                return Err(anyhow::anyhow!("TXTR chunk must be parsed before calling load_background!"));
-            }
-        } else {
+        self.parse_txtr()?;
+        let (Some(bgnd), Some(txtr)) = (self.bgnd.get_mut(), self.txtr.get()) else {
             return Err(anyhow::anyhow!("BGND chunk must be parsed before calling load_background!"));
+        };
+
+        let source = TextureSource { txtr, game_dir: self.game_dir.as_deref(), cache: &self.texture_cache };
+        let name = &name.into();
+        if let Some(bg) = bgnd.backgrounds.get_mut(name) {
+            DataWin::load_background_raw(&source, &self.buf, bg, name, &self.bgnd_rewrap_columns)?;
         }
 
         Ok(())
     }
 
     pub fn load_backgrounds(&mut self) -> anyhow::Result<()> {
+        self.load_backgrounds_where(|_| true)?;
+        Ok(())
+    }
 
-        if let Some(bgnd) = &mut self.bgnd {
-            // we don't actually use the values already stored in TPAG since the sprite only knows the address, not the index...
-            //   not really sure if it would be worth adding a hashmap or something to save the TPAG entries' addresses so we can look them up here?
-
-            // if let Some(tpag) = &mut self.tpag {
-                if let Some(txtr) = &mut self.txtr {
-                    for (name, bg) in &mut bgnd.backgrounds {
-                        DataWin::load_background_raw(txtr, &mut self.buf, bg, name, &mut self.bgnd_rewrap_columns)?;
-                    }
-                } else {
-                    return Err(anyhow::anyhow!("TXTR chunk must be parsed before calling load_backgrounds!"));
-                }
-            // }
-        } else {
+    /// Loads the backgrounds whose name matches a glob pattern (eg. `bg_castle_*`), returning their names.
+    pub fn load_backgrounds_matching(&mut self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        let pattern = glob_pattern(pattern)?;
+        self.load_backgrounds_where(|name| pattern.matches(name))
+    }
+
+    /// Loads the backgrounds whose name `pred` returns true for (eg. a regex match), returning their names.
+    pub fn load_backgrounds_where<P: Fn(&str) -> bool + Sync>(&mut self, pred: P) -> anyhow::Result<Vec<String>> {
+        self.parse_txtr()?;
+        let (Some(bgnd), Some(txtr)) = (self.bgnd.get_mut(), self.txtr.get()) else {
             return Err(anyhow::anyhow!("BGND chunk must be parsed before calling load_backgrounds!"));
-        }
+        };
 
-        Ok(())
+        let source = TextureSource { txtr, game_dir: self.game_dir.as_deref(), cache: &self.texture_cache };
+        let data = &self.buf;
+        let bgnd_rewrap_columns = &self.bgnd_rewrap_columns;
+        for_each_matching(&mut bgnd.backgrounds, &self.hooks, Phase::Backgrounds, pred, |name, bg| DataWin::load_background_raw(&source, data, bg, name, bgnd_rewrap_columns))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn entries() -> HashMap<String, u32> {
+        (0..10).map(|i| (format!("spr_{}", i), 0)).collect()
+    }
+
+    #[test]
+    fn filtered_loads_only_count_matching_entries() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let hooks = Hooks {
+            progress: Some(Arc::new({
+                let reports = Arc::clone(&reports);
+                move |phase, done, total| reports.lock().unwrap().push((phase, done, total))
+            })),
+            cancel: None,
+        };
+
+        let mut map = entries();
+        let names = for_each_matching(&mut map, &hooks, Phase::Sprites, |name| name < "spr_3", |_, v| {
+            *v += 1;
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(names, ["spr_0", "spr_1", "spr_2"]);
+        assert_eq!(map.values().sum::<u32>(), 3);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 4); // the start, then one per entry
+        assert!(reports.iter().all(|(phase, done, total)| *phase == Phase::Sprites && *total == 3 && done <= total));
+        assert_eq!(reports.iter().map(|(_, done, _)| *done).max(), Some(3));
+    }
+
+    #[test]
+    fn cancelled_loads_stop_before_any_entry() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let hooks = Hooks { progress: None, cancel: Some(cancel) };
+
+        let mut map = entries();
+        let err = for_each_matching(&mut map, &hooks, Phase::Sprites, |_| true, |_, v| {
+            *v += 1;
+            Ok(())
+        }).unwrap_err();
+
+        assert!(err.is::<Cancelled>());
+        assert_eq!(map.values().sum::<u32>(), 0);
+    }
+}
@@ -0,0 +1,32 @@
+//! Logging macros that forward to the `log` crate when the `log` feature is enabled, and compile to nothing otherwise.
+
+#[cfg(feature = "log")]
+macro_rules! info {
+    ($($arg:tt)*) => { ::log::info!(target: "dr_extract", $($arg)*) };
+}
+
+#[cfg(feature = "log")]
+macro_rules! debug {
+    ($($arg:tt)*) => { ::log::debug!(target: "dr_extract", $($arg)*) };
+}
+
+#[cfg(feature = "log")]
+macro_rules! trace {
+    ($($arg:tt)*) => { ::log::trace!(target: "dr_extract", $($arg)*) };
+}
+
+// still type check the arguments (and count them as used) without logging anything
+#[cfg(not(feature = "log"))]
+macro_rules! info {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
\ No newline at end of file
@@ -0,0 +1,137 @@
+//! Progress reporting and cancellation for long loads, see `DataWin::set_progress` and `DataWin::set_cancel_token`.
+
+use std::{fmt, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}};
+
+/// What is currently being loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Parse([u8; 4]), // parsing the chunk with this name
+    Spritesheets,
+    Sprites,
+    Backgrounds,
+    Fonts,
+    Sounds,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Parse(id) => write!(f, "parsing {}", String::from_utf8_lossy(id)),
+            Phase::Spritesheets => write!(f, "loading spritesheets"),
+            Phase::Sprites => write!(f, "loading sprites"),
+            Phase::Backgrounds => write!(f, "loading backgrounds"),
+            Phase::Fonts => write!(f, "loading fonts"),
+            Phase::Sounds => write!(f, "loading sounds"),
+        }
+    }
+}
+
+/// Receives progress updates. `report` is called with `done == 0` when a phase starts, then after each entry.
+///
+/// With the `parallel` feature this is called from multiple threads, and `done` may arrive out of order.
+pub trait Progress: Send + Sync {
+    fn report(&self, phase: Phase, done: usize, total: usize);
+}
+
+impl<F: Fn(Phase, usize, usize) + Send + Sync> Progress for F {
+    fn report(&self, phase: Phase, done: usize, total: usize) {
+        self(phase, done, total);
+    }
+}
+
+/// Cancels a load from another thread. The loaders check this between entries and return a `Cancelled` error.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Un-cancels the token, so it can be used for the next load.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The error returned when a load is cancelled. Check for it with `err.is::<Cancelled>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Loading was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    pub progress: Option<Arc<dyn Progress>>,
+    pub cancel: Option<CancelToken>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("progress", &self.progress.is_some())
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+impl Hooks {
+    pub fn check(&self) -> anyhow::Result<()> {
+        match &self.cancel {
+            Some(cancel) if cancel.is_cancelled() => Err(Cancelled.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts a phase with `total` entries.
+    pub fn start(&self, phase: Phase, total: usize) -> Tracker<'_> {
+        if let Some(progress) = &self.progress {
+            progress.report(phase, 0, total);
+        }
+
+        Tracker {
+            hooks: self,
+            phase,
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+}
+
+pub(crate) struct Tracker<'a> {
+    hooks: &'a Hooks,
+    phase: Phase,
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl Tracker<'_> {
+    /// Runs `f` for one entry (unless cancelled), then reports it as done.
+    pub fn step<T>(&self, f: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+        self.hooks.check()?;
+        let result = f()?;
+
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(progress) = &self.hooks.progress {
+            progress.report(self.phase, done, self.total);
+        }
+
+        Ok(result)
+    }
+}
\ No newline at end of file
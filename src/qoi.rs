@@ -0,0 +1,155 @@
+//! Decoder for the QOI variant GM uses for texture pages ("fioq" header).
+//!
+//! This is based on an early draft of the QOI spec, so it is not compatible with the final format
+//! (different opcodes, two's complement diffs, and a custom header).
+
+use image::{DynamicImage, RgbaImage};
+
+const QOI_INDEX: u8 = 0x00; // 00xxxxxx
+const QOI_RUN_8: u8 = 0x40; // 010xxxxx
+const QOI_RUN_16: u8 = 0x60; // 011xxxxx
+const QOI_DIFF_8: u8 = 0x80; // 10xxxxxx
+const QOI_DIFF_16: u8 = 0xc0; // 110xxxxx
+const QOI_DIFF_24: u8 = 0xe0; // 1110xxxx
+const QOI_COLOR: u8 = 0xf0; // 1111xxxx
+
+const QOI_MASK_2: u8 = 0xc0;
+const QOI_MASK_3: u8 = 0xe0;
+const QOI_MASK_4: u8 = 0xf0;
+
+pub(crate) const HEADER_LEN: usize = 12;
+
+/// Sign-extends the `bits` wide value at `shift` in `val`.
+#[allow(clippy::cast_possible_wrap)]
+fn signed(val: u32, shift: u32, bits: u32) -> u8 {
+    ((((val >> shift) << (32 - bits)) as i32) >> (32 - bits)) as u8
+}
+
+fn next(data: &mut impl Iterator<Item = u8>) -> anyhow::Result<u8> {
+    data.next().ok_or_else(|| anyhow::anyhow!("QOI data is truncated!"))
+}
+
+/// Decodes a "fioq" image. `bytes` must start at the header.
+pub(crate) fn decode(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != b"fioq" {
+        return Err(anyhow::anyhow!("Invalid QOI header!"));
+    }
+
+    let width = u32::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+    let height = u32::from(u16::from_le_bytes([bytes[6], bytes[7]]));
+    let length = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+
+    let data = bytes.get(HEADER_LEN..HEADER_LEN + length).ok_or_else(|| anyhow::anyhow!("QOI data is truncated!"))?;
+    let mut data = data.iter().copied();
+
+    let mut img = RgbaImage::new(width, height);
+    let mut index = [[0_u8; 4]; 64];
+    let (mut r, mut g, mut b, mut a) = (0_u8, 0_u8, 0_u8, 255_u8);
+    let mut run = 0_u32;
+
+    for px in img.pixels_mut() {
+        if run > 0 {
+            run -= 1;
+        } else if data.len() > 0 {
+            let b1 = next(&mut data)?;
+
+            if b1 & QOI_MASK_2 == QOI_INDEX {
+                let [ir, ig, ib, ia] = index[usize::from(b1 ^ QOI_INDEX)];
+                r = ir;
+                g = ig;
+                b = ib;
+                a = ia;
+            } else if b1 & QOI_MASK_3 == QOI_RUN_8 {
+                run = u32::from(b1 & 0x1f);
+            } else if b1 & QOI_MASK_3 == QOI_RUN_16 {
+                let b2 = next(&mut data)?;
+                run = ((u32::from(b1 & 0x1f) << 8) | u32::from(b2)) + 32;
+            } else if b1 & QOI_MASK_2 == QOI_DIFF_8 {
+                let merged = u32::from(b1);
+                r = r.wrapping_add(signed(merged, 4, 2));
+                g = g.wrapping_add(signed(merged, 2, 2));
+                b = b.wrapping_add(signed(merged, 0, 2));
+            } else if b1 & QOI_MASK_3 == QOI_DIFF_16 {
+                let merged = (u32::from(b1) << 8) | u32::from(next(&mut data)?);
+                r = r.wrapping_add(signed(merged, 8, 5));
+                g = g.wrapping_add(signed(merged, 4, 4));
+                b = b.wrapping_add(signed(merged, 0, 4));
+            } else if b1 & QOI_MASK_4 == QOI_DIFF_24 {
+                let merged = (u32::from(b1) << 16) | (u32::from(next(&mut data)?) << 8) | u32::from(next(&mut data)?);
+                r = r.wrapping_add(signed(merged, 15, 5));
+                g = g.wrapping_add(signed(merged, 10, 5));
+                b = b.wrapping_add(signed(merged, 5, 5));
+                a = a.wrapping_add(signed(merged, 0, 5));
+            } else if b1 & QOI_MASK_4 == QOI_COLOR {
+                if b1 & 8 != 0 {
+                    r = next(&mut data)?;
+                }
+                if b1 & 4 != 0 {
+                    g = next(&mut data)?;
+                }
+                if b1 & 2 != 0 {
+                    b = next(&mut data)?;
+                }
+                if b1 & 1 != 0 {
+                    a = next(&mut data)?;
+                }
+            }
+
+            index[usize::from((r ^ g ^ b ^ a) & 63)] = [r, g, b, a];
+        }
+
+        px.0 = [r, g, b, a];
+    }
+
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qoi(width: u16, height: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = b"fioq".to_vec();
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn decodes_every_opcode() {
+        let data = [
+            0xff, 10, 20, 30, 255, // color
+            0x9c, // diff 8: +1, -1, 0
+            0xdd, 0x2f, // diff 16: -3, +2, -1
+            0xe1, 0x00, 0x3b, // diff 24: +2, 0, +1, -5
+            0x39, // index 57, the second color
+            0x42, // run 8: this pixel and 2 more
+            0xf2, 99, // color with only blue
+            0x60, 3, // run 16: this pixel and 3 + 32 more
+        ];
+        let img = decode(&qoi(45, 1, &data)).unwrap().to_rgba8();
+
+        let mut expected = vec![[10, 20, 30, 255], [11, 19, 30, 255], [8, 21, 29, 255], [10, 21, 30, 250]];
+        expected.extend([[11, 19, 30, 255]; 4]);
+        expected.extend([[11, 19, 99, 255]; 37]);
+        assert_eq!(img.pixels().map(|px| px.0).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn pixels_after_the_data_repeat_the_last_one() {
+        let img = decode(&qoi(3, 2, &[0xf8, 7])).unwrap().to_rgba8();
+        assert!(img.pixels().all(|px| px.0 == [7, 0, 0, 255]));
+    }
+
+    #[test]
+    fn rejects_bad_headers_and_truncated_data() {
+        assert!(decode(b"fioq\x01\0\x01\0").is_err());
+        assert!(decode(&qoi(1, 1, &[0xff, 1, 2, 3])[..15]).is_err());
+        assert!(decode(&qoi(1, 1, &[0xff, 1, 2])).is_err()); // the opcode needs 4 bytes
+        let mut bad = qoi(1, 1, &[]);
+        bad[0] = b'x';
+        assert!(decode(&bad).is_err());
+    }
+}
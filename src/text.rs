@@ -0,0 +1,194 @@
+//! Drawing text with a loaded font, laid out the way GM's `draw_text` does it.
+
+use std::convert::TryFrom;
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage, imageops::{self, FilterType}};
+
+use crate::chunk::{FontEntry, Glyph};
+
+/// A glyph placed on a line, in unscaled pixels.
+struct Placed<'a> {
+    glyph: &'a Glyph,
+    x: i32,
+    y: i32,
+}
+
+impl FontEntry {
+    /// The distance between lines, from `line_height` if the font has it, otherwise the tallest glyph.
+    pub(crate) fn line_spacing(&self) -> u32 {
+        self.line_height.unwrap_or_else(|| self.glyphs.values().map(|gly| u32::from(gly.height)).max().unwrap_or(0))
+    }
+
+    /// The characters in `text` that this font has no glyph for (besides newlines), in the order they first appear.
+    /// Useful for checking that a translation can be drawn with the game's fonts.
+    #[must_use]
+    pub fn covers(&self, text: &str) -> Vec<char> {
+        let mut missing = Vec::new();
+        for ch in text.chars() {
+            if ch != '\n' && !self.glyphs.contains_key(&ch) && !missing.contains(&ch) {
+                missing.push(ch);
+            }
+        }
+        missing
+    }
+
+    /// Lays out `text` and returns the glyphs along with the (unscaled) size of the text.
+    fn layout(&self, text: &str) -> (Vec<Placed<'_>>, u32, u32) {
+        let line_spacing = i32::try_from(self.line_spacing()).unwrap_or(i32::MAX);
+
+        let mut placed = Vec::new();
+        let mut width = 0;
+        let mut lines = 0;
+        for line in text.split('\n') {
+            let y = lines * line_spacing;
+            lines += 1;
+
+            let mut x = 0;
+            let mut prev = None;
+            for ch in line.chars() {
+                // like GM, characters the font doesn't have are skipped entirely
+                let Some(glyph) = self.glyphs.get(&ch) else { continue };
+
+                if let Some(prev) = prev {
+                    x += glyph.kerning.iter().find(|(other, _)| *other == prev).map_or(0, |(_, amount)| i32::from(*amount));
+                }
+
+                placed.push(Placed { glyph, x: x + i32::from(glyph.offset), y });
+                width = width.max(x + i32::from(glyph.offset) + i32::from(glyph.width));
+
+                x += i32::from(glyph.shift);
+                width = width.max(x);
+                prev = Some(glyph.code);
+            }
+        }
+
+        (placed, u32::try_from(width).unwrap_or(0), u32::try_from(lines * line_spacing).unwrap_or(0))
+    }
+
+    /// The size in pixels of `text` drawn with `render_text`, including `scale_x`/`scale_y`.
+    #[must_use]
+    pub fn measure_text(&self, text: &str) -> (u32, u32) {
+        let (_, width, height) = self.layout(text);
+        (scale(width, self.scale_x), scale(height, self.scale_y))
+    }
+
+    /// Draws `text` in `color` on a transparent image, using the glyphs' advances, offsets and kerning, and the font's `scale_x`/`scale_y`.
+    /// Lines are split on `\n`, and characters that aren't in the font are skipped.
+    /// The font must be loaded first (see `DataWin::load_font`).
+    pub fn render_text(&self, text: &str, color: Rgba<u8>) -> anyhow::Result<DynamicImage> {
+        let (placed, width, height) = self.layout(text);
+        let mut out = RgbaImage::new(scale(width, self.scale_x), scale(height, self.scale_y));
+
+        for Placed { glyph, x, y } in placed {
+            let texture = glyph.texture.as_ref().ok_or_else(|| anyhow::anyhow!("Font must be loaded before rendering text!"))?;
+            if glyph.width == 0 || glyph.height == 0 {
+                continue;
+            }
+
+            let (w, h) = (scale(glyph.width.into(), self.scale_x).max(1), scale(glyph.height.into(), self.scale_y).max(1));
+            let texture = if (w, h) == texture.dimensions() {
+                texture.to_rgba8()
+            } else {
+                imageops::resize(texture, w, h, FilterType::Nearest)
+            };
+
+            let x = scale_pos(x, self.scale_x);
+            let y = scale_pos(y, self.scale_y);
+            for (gx, gy, px) in texture.enumerate_pixels() {
+                let (ox, oy) = (x + i64::from(gx), y + i64::from(gy));
+                if let (Ok(ox), Ok(oy)) = (u32::try_from(ox), u32::try_from(oy)) {
+                    if ox < out.width() && oy < out.height() {
+                        blend(out.get_pixel_mut(ox, oy), tint(*px, color));
+                    }
+                }
+            }
+        }
+
+        Ok(DynamicImage::ImageRgba8(out))
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn scale(len: u32, scale: f32) -> u32 {
+    (len as f32 * scale.abs()).round() as u32
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn scale_pos(pos: i32, scale: f32) -> i64 {
+    (pos as f32 * scale.abs()).round() as i64
+}
+
+/// Multiplies a glyph pixel by the text color, like GM's blending with `draw_set_color`.
+fn tint(px: Rgba<u8>, color: Rgba<u8>) -> Rgba<u8> {
+    let mul = |a: u8, b: u8| ((u16::from(a) * u16::from(b) + 127) / 255) as u8;
+    Rgba([mul(px[0], color[0]), mul(px[1], color[1]), mul(px[2], color[2]), mul(px[3], color[3])])
+}
+
+/// Alpha-blends `src` over `dst`.
+fn blend(dst: &mut Rgba<u8>, src: Rgba<u8>) {
+    let src_a = f32::from(src[3]) / 255.0;
+    let dst_a = f32::from(dst[3]) / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    if out_a <= 0.0 {
+        return;
+    }
+
+    for i in 0..3 {
+        let c = (f32::from(src[i]) * src_a + f32::from(dst[i]) * dst_a * (1.0 - src_a)) / out_a;
+        dst[i] = c.round() as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 'A' (4 wide, advancing 5) and 'V' (5 wide, advancing 6, 2 closer after an 'A'), both 6 tall and solid white.
+    fn font() -> FontEntry {
+        let mut font = FontEntry::with_glyphs(vec![Glyph::new('A', 0, 4, 6, 5), Glyph::new('V', 4, 5, 6, 6)]);
+        for gly in font.glyphs.values_mut() {
+            gly.texture = Some(DynamicImage::ImageRgba8(RgbaImage::from_pixel(gly.width.into(), gly.height.into(), Rgba([255; 4]))));
+        }
+        font.glyphs.get_mut(&'V').unwrap().kerning.push((u16::from(b'A'), -2));
+        font
+    }
+
+    #[test]
+    fn measures_advances_kerning_and_lines() {
+        let font = font();
+        assert_eq!(font.measure_text("AV"), (9, 6));
+        assert_eq!(font.measure_text("VA"), (11, 6));
+        assert_eq!(font.measure_text("A\nVV"), (12, 12));
+        assert_eq!(font.measure_text("A?A"), (10, 6)); // missing characters are skipped
+        assert_eq!(font.measure_text(""), (0, 6));
+    }
+
+    #[test]
+    fn measures_with_the_font_scale() {
+        let mut font = font();
+        font.scale_x = 2.0;
+        font.scale_y = 0.5;
+        assert_eq!(font.measure_text("AV"), (18, 3));
+
+        font.line_height = Some(10);
+        assert_eq!(font.measure_text("A\nA"), (10, 10));
+    }
+
+    #[test]
+    fn renders_tinted_glyphs_where_they_are_placed() {
+        let text = font().render_text("AV", Rgba([255, 0, 0, 128])).unwrap().to_rgba8();
+        assert_eq!(text.dimensions(), (9, 6));
+        assert_eq!(*text.get_pixel(0, 0), Rgba([255, 0, 0, 128]));
+        assert_eq!(*text.get_pixel(3, 5), Rgba([255, 0, 0, 192])); // where 'A' and 'V' overlap
+        assert_eq!(*text.get_pixel(7, 0), Rgba([255, 0, 0, 128]));
+        assert_eq!(text.get_pixel(8, 0)[3], 0);
+    }
+
+    #[test]
+    fn rendering_needs_a_loaded_font() {
+        let font = FontEntry::with_glyphs(vec![Glyph::new('A', 0, 4, 6, 5)]);
+        assert!(font.render_text("A", Rgba([255; 4])).is_err());
+        assert!(font.covers("AB").contains(&'B'));
+    }
+}
@@ -0,0 +1,62 @@
+//! Finding and reading audio group files, with a tiny hand-built data.win.
+
+use std::{fs, path::PathBuf};
+
+use dr_extract::DataWin;
+
+mod common;
+
+/// Writes the data.win and its audio group file (at `music_path`, or audiogroup1.dat) to a fresh directory.
+fn game_dir(test: &str, music_path: Option<&str>, audio_group: &[u8]) -> PathBuf {
+    let dir = common::game_dir(&format!("audio-groups-{}", test), &common::data_win_with_audio_group(music_path));
+    fs::create_dir_all(dir.join("audio")).unwrap();
+    fs::write(dir.join(music_path.unwrap_or("audiogroup1.dat")), audio_group).unwrap();
+    dir
+}
+
+#[test]
+fn groups_are_found_through_agrp() {
+    for music_path in [None, Some("audio/music.dat")] {
+        let data = DataWin::open_dir(game_dir("agrp", music_path, &common::audio_group())).unwrap();
+        assert_eq!(data.audiogroup_count(), 1);
+
+        let agrp = data.parse_agrp().unwrap();
+        let names = agrp.groups.iter().map(|group| group.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["audiogroup_default", "audiogroup_music"]);
+        assert_eq!(agrp.groups[1].file_name(1).as_deref(), Some(music_path.unwrap_or("audiogroup1.dat")));
+
+        let sound = &data.parse_sond().unwrap().sounds["snd_music"];
+        assert_eq!(sound.group(agrp).unwrap().name, "audiogroup_music");
+    }
+}
+
+#[test]
+fn missing_group_files_are_an_error() {
+    let dir = game_dir("missing", None, &common::audio_group());
+    fs::remove_file(dir.join("audiogroup1.dat")).unwrap();
+
+    let err = DataWin::open_dir(&dir).unwrap_err();
+    assert!(err.to_string().contains("audiogroup1.dat for audio group 1 (\"audiogroup_music\")"), "{}", err);
+}
+
+#[test]
+fn group_files_are_read_as_form_files() {
+    let data = DataWin::open_dir(game_dir("form", None, &common::audio_group())).unwrap();
+
+    let ids = data.audiogroup_chunks(1).unwrap().iter().map(|chunk| chunk.id).collect::<Vec<_>>();
+    assert_eq!(ids, [*b"AUDO", *b"EXTR"]);
+    assert_eq!(data.audiogroup_chunk_data(1, *b"EXTR"), Some(&b"hi!!"[..]));
+    assert!(data.audiogroup_chunks(0).is_none());
+    assert!(data.audiogroup_chunks(2).is_none());
+
+    assert_eq!(data.sound_bytes("snd_music").unwrap(), Some(&common::wav()[..]));
+}
+
+#[test]
+fn group_files_need_an_audo_chunk() {
+    let mut group = common::audio_group();
+    group[8..12].copy_from_slice(b"NOPE");
+
+    let err = DataWin::open_dir(game_dir("no-audo", None, &group)).unwrap_err();
+    assert!(err.to_string().contains("Audio group 1 has no AUDO chunk!"), "{}", err);
+}
@@ -0,0 +1,71 @@
+//! Runs `dr-extract extract` on a tiny hand-built data.win.
+#![cfg(feature = "cli")]
+
+use std::{fs, path::PathBuf, process::Command};
+
+mod common;
+
+use common::{data_win, game_dir, wav};
+
+/// Writes the data.win to a fresh directory and runs `dr-extract extract <kind>` on it, returning the output directory.
+fn extract(kind: &str, extra: &[&str]) -> PathBuf {
+    let dir = game_dir(&format!("cli-{}-{}", kind, extra.join("")), &data_win());
+
+    let out = Command::new(env!("CARGO_BIN_EXE_dr-extract"))
+        .current_dir(&dir)
+        .args(["--quiet", "extract", kind])
+        .args(extra)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "extract {} failed: {}", kind, String::from_utf8_lossy(&out.stderr));
+
+    dir.join("extract")
+}
+
+#[test]
+fn extract_spritesheets() {
+    let out = extract("spritesheets", &[]);
+    assert_eq!(image::open(out.join("spritesheet/0.png")).unwrap().to_rgba8().dimensions(), (4, 4));
+}
+
+#[test]
+fn extract_sprites() {
+    let out = extract("sprites", &[]);
+    assert_eq!(image::open(out.join("sprite/spr_test.png")).unwrap().to_rgba8().dimensions(), (4, 4));
+}
+
+#[test]
+fn extract_backgrounds() {
+    let out = extract("backgrounds", &[]);
+    assert!(out.join("background/bg_test.png").is_file());
+}
+
+#[test]
+fn extract_sounds() {
+    let out = extract("sounds", &[]);
+    assert_eq!(fs::read(out.join("sound/snd_test.wav")).unwrap(), wav());
+}
+
+#[test]
+fn extract_fonts() {
+    let out = extract("fonts", &[]);
+    let fnt = fs::read_to_string(out.join("font/fnt_test/fnt_test.fnt")).unwrap();
+    assert!(fnt.contains("char id=65 x=0 y=0 width=4 height=4 xoffset=0 yoffset=0 xadvance=5"), "{}", fnt);
+    assert!(out.join("font/fnt_test/65.png").is_file());
+}
+
+#[test]
+fn extract_all() {
+    let out = extract("all", &["--filter", "*test"]);
+    for file in ["sprite/spr_test.png", "background/bg_test.png", "sound/snd_test.wav", "font/fnt_test/fnt_test_0.png"] {
+        assert!(out.join(file).is_file(), "{} is missing", file);
+    }
+}
+
+#[test]
+fn extract_scaled_fonts() {
+    let out = extract("fonts", &["--scale", "2"]);
+    let fnt = fs::read_to_string(out.join("font/fnt_test/fnt_test.fnt")).unwrap();
+    assert!(fnt.contains("char id=65 x=0 y=0 width=8 height=8 xoffset=0 yoffset=0 xadvance=10"), "{}", fnt);
+    assert_eq!(image::open(out.join("font/fnt_test/fnt_test_0.png")).unwrap().to_rgba8().dimensions(), (8, 8));
+}
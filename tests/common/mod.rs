@@ -0,0 +1,245 @@
+//! A tiny hand-built data.win for the integration tests.
+#![allow(dead_code)]
+
+use std::{fs, path::{Path, PathBuf}};
+
+use dr_extract::DataWin;
+use image::{DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+
+#[path = "../../src/chunk/testing.rs"]
+mod testing;
+
+pub use testing::Writer;
+
+/// Writes `data_win` to a fresh directory named `name`, returning the directory.
+pub fn game_dir(name: &str, data_win: &[u8]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("data.win"), data_win).unwrap();
+    dir
+}
+
+/// Opens `data_win()` from a fresh directory named `name`.
+pub fn open(name: &str) -> DataWin {
+    DataWin::open_dir(game_dir(name, &data_win())).unwrap()
+}
+
+/// Writes a data.win with one of each asset: a 4x4 texture page, used by a sprite, a background and a font with one glyph ('A'),
+/// and a sound with a tiny embedded WAV.
+pub fn data_win() -> Vec<u8> {
+    build_data_win(false)
+}
+
+/// Like `data_win`, but the sound is stored outside of it, in snd_test.wav.
+pub fn data_win_with_external_sound() -> Vec<u8> {
+    build_data_win(true)
+}
+
+fn build_data_win(external_sound: bool) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(b"FORM");
+    let form_len = w.placeholder();
+
+    // strings are referred to by pointers to their characters (after the length)
+    let mut strings = Vec::new();
+    w.chunk(*b"STRG", |w| {
+        for s in ["spr_test", "bg_test", "fnt_test", "Arial", "snd_test", "", "snd_test.wav"] {
+            w.u32(s.len() as u32);
+            strings.push(w.pos());
+            w.bytes(s.as_bytes());
+            w.bytes(&[0]);
+        }
+    });
+    let [spr_name, bg_name, fnt_name, system_name, snd_name, snd_type, snd_file] = strings[..] else { unreachable!() };
+
+    w.chunk(*b"TXTR", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        w.u32(0);
+        w.u32(0);
+        w.u32(w.pos() + 4); // the PNG right after the entry
+
+        let mut png = Vec::new();
+        let sheet = RgbaImage::from_fn(4, 4, |x, y| Rgba([(x * 60) as u8, (y * 60) as u8, 0, 255]));
+        DynamicImage::ImageRgba8(sheet).write_to(&mut png, ImageOutputFormat::Png).unwrap();
+        w.bytes(&png);
+    });
+
+    let mut tpag_addr = 0;
+    w.chunk(*b"TPAG", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        tpag_addr = w.pos();
+        for v in [0, 0, 4, 4, 0, 0, 4, 4, 4, 4, 0] {
+            w.u16(v);
+        }
+    });
+
+    w.chunk(*b"SPRT", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        w.u32(spr_name);
+        for v in [4, 4, 0, 3, 3, 0] {
+            w.u32(v); // size, margins
+        }
+        for _ in 0..7 {
+            w.u32(0); // flags, bbox mode, sep masks, origin
+        }
+        w.u32(1);
+        w.u32(tpag_addr);
+        w.u32(0); // no masks
+    });
+
+    w.chunk(*b"BGND", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        w.u32(bg_name);
+        for _ in 0..3 {
+            w.u32(0);
+        }
+        w.u32(tpag_addr);
+        for v in [0, 2, 2, 0, 0, 2, 0, 0, 0, 0] {
+            w.u32(v); // 2x2 tiles in 2 columns, with none listed
+        }
+    });
+
+    w.chunk(*b"FONT", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        w.u32(fnt_name);
+        w.u32(system_name);
+        w.f32(-12.0);
+        w.u32(0);
+        w.u32(0);
+        w.u16(32);
+        w.bytes(&[1, 1]); // charset, antialiasing
+        w.u32(127);
+        w.u32(tpag_addr);
+        w.f32(1.0);
+        w.f32(1.0);
+        w.u32(0);
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        for v in [u16::from(b'A'), 0, 0, 4, 4, 5, 0] {
+            w.u16(v);
+        }
+    });
+
+    w.chunk(*b"SOND", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        w.u32(snd_name);
+        w.u32(if external_sound { 0x64 } else { 0x65 });
+        w.u32(snd_type);
+        w.u32(snd_file);
+        w.u32(0);
+        w.f32(1.0);
+        w.f32(1.0);
+        w.u32(0);
+        w.i32(if external_sound { -1 } else { 0 });
+    });
+
+    w.chunk(*b"AUDO", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        let wav = wav();
+        w.u32(wav.len() as u32);
+        w.bytes(&wav);
+    });
+
+    let len = w.pos() - 8;
+    w.patch(form_len, len);
+    w.buf
+}
+
+/// Writes a data.win whose AGRP chunk lists the default group and "audiogroup_music", which `snd_music` is in.
+/// With a `music_path`, the groups have paths (like GM 2024.14+), otherwise the music group is in audiogroup1.dat.
+pub fn data_win_with_audio_group(music_path: Option<&str>) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(b"FORM");
+    let form_len = w.placeholder();
+
+    let mut strings = Vec::new();
+    w.chunk(*b"STRG", |w| {
+        for s in ["audiogroup_default", "audiogroup_music", "snd_music", "", music_path.unwrap_or("")] {
+            w.u32(s.len() as u32);
+            strings.push(w.pos());
+            w.bytes(s.as_bytes());
+            w.bytes(&[0]);
+        }
+    });
+    let [default_name, music_name, snd_name, empty, path] = strings[..] else { unreachable!() };
+
+    w.chunk(*b"AGRP", |w| {
+        let entry_len = if music_path.is_some() { 8 } else { 4 };
+        w.u32(2);
+        w.u32(w.pos() + 8);
+        w.u32(w.pos() + 4 + entry_len);
+        for (name, path) in [(default_name, empty), (music_name, path)] {
+            w.u32(name);
+            if music_path.is_some() {
+                w.u32(path);
+            }
+        }
+    });
+
+    w.chunk(*b"SOND", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        w.u32(snd_name);
+        w.u32(0);
+        w.u32(empty);
+        w.u32(empty);
+        w.u32(0);
+        w.f32(1.0);
+        w.f32(1.0);
+        w.u32(1); // the music group
+        w.u32(0);
+    });
+
+    w.chunk(*b"AUDO", |w| w.u32(0));
+
+    let len = w.pos() - 8;
+    w.patch(form_len, len);
+    w.buf
+}
+
+/// An audio group file with `wav()` as its only sound, and an extra `EXTR` chunk after AUDO.
+pub fn audio_group() -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(b"FORM");
+    let form_len = w.placeholder();
+
+    w.chunk(*b"AUDO", |w| {
+        w.u32(1);
+        w.u32(w.pos() + 4);
+        let wav = wav();
+        w.u32(wav.len() as u32);
+        w.bytes(&wav);
+    });
+    w.chunk(*b"EXTR", |w| w.bytes(b"hi!!"));
+
+    let len = w.pos() - 8;
+    w.patch(form_len, len);
+    w.buf
+}
+
+/// A mono 8 kHz WAV with 4 samples.
+pub fn wav() -> Vec<u8> {
+    let mut w = Writer::default();
+    w.bytes(b"RIFF");
+    w.u32(36 + 8);
+    w.bytes(b"WAVEfmt ");
+    w.u32(16);
+    w.u16(1);
+    w.u16(1);
+    w.u32(8000);
+    w.u32(16000);
+    w.u16(2);
+    w.u16(16);
+    w.bytes(b"data");
+    w.u32(8);
+    w.bytes(&[0; 8]);
+    w.buf
+}
@@ -0,0 +1,54 @@
+//! The `&self` loaders and their caches, on a tiny hand-built data.win.
+
+use std::{collections::HashMap, sync::{Arc, Barrier}, thread};
+
+use image::GenericImageView;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn rewrapping_backgrounds_replaces_cached_textures() {
+    let mut data = open("lazy-rewrap");
+    assert_eq!(data.background_texture("bg_test").unwrap().unwrap().dimensions(), (4, 4));
+
+    data.add_background_rewrap_columns(HashMap::from([("bg_test".to_string(), 4)]));
+    assert_eq!(data.background_texture("bg_test").unwrap().unwrap().dimensions(), (8, 2));
+}
+
+#[test]
+fn sprites_are_decoded_once_across_threads() {
+    let data = open("lazy-threads");
+    let barrier = Barrier::new(8);
+
+    let frames = thread::scope(|s| {
+        let handles = (0..8).map(|_| s.spawn(|| {
+            barrier.wait();
+            data.sprite_frame("spr_test", 0).unwrap()
+        })).collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+    });
+
+    assert!(frames.iter().all(|frame| Arc::ptr_eq(frame, &frames[0])));
+}
+
+#[test]
+fn loaded_sprites_and_backgrounds_are_read_directly() {
+    let mut data = open("lazy-loaded");
+    let lazy = data.sprite_frame("spr_test", 0).unwrap();
+    data.load_sprite("spr_test").unwrap();
+    data.parse_bgnd().unwrap();
+    data.load_background("bg_test").unwrap();
+
+    // the loaded textures aren't cached, so every call gets a fresh copy of them
+    let frames = data.sprite_frames("spr_test").unwrap();
+    assert!(!Arc::ptr_eq(&frames[0], &data.sprite_frames("spr_test").unwrap()[0]));
+    assert_eq!(frames[0].to_rgba8(), lazy.to_rgba8());
+    assert_eq!(data.sprite_frame("spr_test", 0).unwrap().to_rgba8(), lazy.to_rgba8());
+    assert!(data.sprite_frame("spr_test", 1).is_err());
+
+    let bg = data.background_texture("bg_test").unwrap().unwrap();
+    assert!(!Arc::ptr_eq(&bg, &data.background_texture("bg_test").unwrap().unwrap()));
+    assert_eq!(bg.dimensions(), (4, 4));
+}
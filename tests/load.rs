@@ -0,0 +1,54 @@
+//! The `&mut self` bulk loaders, on a tiny hand-built data.win.
+
+use dr_extract::chunk::{AudioType, BackgroundState, SpriteState};
+
+mod common;
+
+use common::open;
+
+#[test]
+fn bulk_loaders_load_every_asset() {
+    let mut data = open("load-all");
+    data.parse_sprt().unwrap();
+    data.parse_bgnd().unwrap();
+    data.parse_font().unwrap();
+    data.parse_sond().unwrap();
+    data.parse_audo().unwrap();
+    data.load_sprites().unwrap();
+    data.load_backgrounds().unwrap();
+    data.load_fonts().unwrap();
+    data.load_sounds().unwrap();
+
+    let lazy = open("load-all-lazy");
+    let SpriteState::Loaded { textures } = &data.parse_sprt().unwrap().sprites["spr_test"].textures else { panic!("sprite wasn't loaded") };
+    assert_eq!(textures[0].to_rgba8(), lazy.sprite_frame("spr_test", 0).unwrap().to_rgba8());
+
+    let BackgroundState::Loaded { texture } = &data.parse_bgnd().unwrap().backgrounds["bg_test"].texture else { panic!("background wasn't loaded") };
+    assert_eq!(texture.to_rgba8(), lazy.background_texture("bg_test").unwrap().unwrap().to_rgba8());
+
+    assert!(data.parse_font().unwrap().fonts["fnt_test"].texture.is_some());
+    assert!(matches!(data.parse_sond().unwrap().sounds["snd_test"].audio_data, Some(AudioType::Internal { .. })));
+}
+
+#[test]
+fn matching_loaders_only_load_matching_assets() {
+    let mut data = open("load-matching");
+    data.parse_sprt().unwrap();
+    data.parse_bgnd().unwrap();
+    data.parse_font().unwrap();
+    data.parse_sond().unwrap();
+    data.parse_audo().unwrap();
+
+    assert_eq!(data.load_sprites_matching("spr_*").unwrap(), ["spr_test"]);
+    assert_eq!(data.load_backgrounds_matching("bg_t?st").unwrap(), ["bg_test"]);
+    assert!(data.load_fonts_matching("spr_*").unwrap().is_empty());
+    assert!(data.load_sounds_matching("snd_[!t]*").unwrap().is_empty());
+
+    assert!(matches!(data.parse_sprt().unwrap().sprites["spr_test"].textures, SpriteState::Loaded { .. }));
+    assert!(matches!(data.parse_bgnd().unwrap().backgrounds["bg_test"].texture, BackgroundState::Loaded { .. }));
+    assert!(data.parse_font().unwrap().fonts["fnt_test"].texture.is_none());
+    assert!(data.parse_sond().unwrap().sounds["snd_test"].audio_data.is_none());
+
+    let err = data.load_sprites_matching("spr_[").unwrap_err();
+    assert!(err.to_string().contains("Invalid pattern \"spr_[\""), "{}", err);
+}
@@ -0,0 +1,44 @@
+//! The `log` events emitted while opening and parsing a data.win.
+#![cfg(feature = "log")]
+
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+mod common;
+
+static RECORDS: Mutex<Vec<(Level, String, String)>> = Mutex::new(Vec::new());
+
+struct Recorder;
+
+impl Log for Recorder {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS.lock().unwrap().push((record.level(), record.target().to_string(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn opening_and_parsing_is_logged() {
+    log::set_logger(&Recorder).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let data = common::open("log");
+    data.parse_sprt().unwrap();
+
+    let records = RECORDS.lock().unwrap();
+    assert!(records.iter().all(|(_, target, _)| target == "dr_extract"));
+
+    let logged = |level, start: &str| records.iter().any(|(l, _, message)| *l == level && message.starts_with(start));
+    assert!(logged(Level::Info, "Given "), "{:?}", records);
+    for chunk in ["STRG", "TXTR", "TPAG", "SPRT", "BGND", "FONT", "SOND", "AUDO"] {
+        assert!(logged(Level::Debug, &format!("chunk {} at ", chunk)), "{} wasn't listed: {:?}", chunk, records);
+    }
+    assert!(logged(Level::Debug, "parsing SPRT at "), "{:?}", records);
+    assert!(logged(Level::Debug, "parsed SPRT in "), "{:?}", records);
+}
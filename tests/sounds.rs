@@ -0,0 +1,52 @@
+//! Reading embedded and external sounds, with a tiny hand-built data.win.
+
+use std::{fs, path::PathBuf};
+
+use dr_extract::{DataWin, chunk::AudioFormat};
+
+mod common;
+
+fn game_dir(test: &str, data_win: &[u8]) -> PathBuf {
+    common::game_dir(&format!("sounds-{}", test), data_win)
+}
+
+#[test]
+fn sound_flags_are_decoded() {
+    let data = DataWin::open_dir(game_dir("flags", &common::data_win())).unwrap();
+    let flags = data.parse_sond().unwrap().sounds["snd_test"].flags;
+    assert!(flags.is_embedded() && !flags.is_compressed() && !flags.is_decompressed_on_load() && flags.is_regular());
+}
+
+#[test]
+fn external_sounds_are_read_from_the_game_directory() {
+    let dir = game_dir("external", &common::data_win_with_external_sound());
+    fs::write(dir.join("snd_test.wav"), common::wav()).unwrap();
+    let data = DataWin::open_dir(&dir).unwrap();
+
+    let sound = &data.parse_sond().unwrap().sounds["snd_test"];
+    assert!(!sound.flags.is_embedded());
+    assert_eq!(sound.external_file(), "snd_test.wav");
+
+    assert_eq!(data.sound_bytes("snd_test").unwrap(), None);
+    assert_eq!(&*data.sound_data("snd_test").unwrap(), &common::wav()[..]);
+
+    let info = data.sound_info("snd_test").unwrap();
+    assert_eq!((info.format, info.sample_rate, info.channels, info.duration), (AudioFormat::Wav, Some(8000), Some(1), Some(0.0005)));
+}
+
+#[test]
+fn missing_external_sounds_are_an_error() {
+    let data = DataWin::open_dir(game_dir("missing", &common::data_win_with_external_sound())).unwrap();
+
+    let err = data.sound_data("snd_test").unwrap_err();
+    assert!(err.to_string().starts_with("Failed to read external sound \"snd_test\" (snd_test.wav)"), "{}", err);
+    assert!(data.sound_info("snd_test").is_err());
+}
+
+#[cfg(feature = "audio")]
+#[test]
+fn sounds_render_with_their_volume_and_pitch() {
+    let data = DataWin::open_dir(game_dir("render", &common::data_win())).unwrap();
+    let pcm = data.render_sound("snd_test").unwrap();
+    assert_eq!((pcm.sample_rate, pcm.channels, pcm.samples.as_slice()), (8000, 1, &[0.0; 4][..]));
+}